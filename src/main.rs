@@ -4,6 +4,7 @@ use std::path::Path;
 extern crate fstrings;
 
 use console::StyledObject;
+use shared::config::find_config_path;
 
 pub mod shared;
 pub mod subcmd;
@@ -34,10 +35,28 @@ fn main() {
     let cso = CommonStyledObjects::new();
 
     let config_arg = clap::Arg::new("config")
-        .required(true)
+        .required(false)
         .value_name("FILE")
         .value_hint(clap::ValueHint::FilePath)
-        .help("JSON file specifying related files for a heda project");
+        .help("JSON file specifying related files for a heda project")
+        .long_help(
+            "JSON file specifying related files for a heda project. \
+If omitted, uses HEDA_CONFIG, or else the first heda.json or config.json \
+found in the current directory or its ancestors. \
+Each path in the config can be overridden by an environment variable, \
+such as HEDA_DATA_JSON for dataJson.",
+        );
+
+    let dataset_arg = clap::Arg::new("dataset")
+        .long("dataset")
+        .required(false)
+        .value_name("NAME")
+        .help("Only use this dataset of a workspace config")
+        .long_help(
+            "Only use this dataset of a workspace config. \
+If omitted, every dataset is used, along with the workspace scripts, \
+which receive every dataset by name.",
+        );
 
     let app_m = clap::command!()
         .arg(
@@ -61,17 +80,358 @@ fn main() {
                         .value_hint(clap::ValueHint::DirPath)
                         .help("Where the files will be initialized")
                         .long_help("Where the files will be initialized. It will fail if any "),
+                )
+                .arg(
+                    clap::Arg::new("from")
+                        .long("from")
+                        .required(false)
+                        .value_name("FILE")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Existing array of objects to start with, as JSON, YAML, TOML or NDJSON, inferring the type and schema"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("infer")
+                .about("Generates type.rs and schema.json from an existing array of objects")
+                .arg(
+                    clap::Arg::new("data")
+                        .required(true)
+                        .value_name("FILE")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Array of objects to infer types from, as JSON, YAML, TOML or NDJSON by its extension"),
+                )
+                .arg(
+                    clap::Arg::new("type")
+                        .long("type")
+                        .required(false)
+                        .value_name("FILE")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Where type.rs will be written, next to the data by default"),
+                )
+                .arg(
+                    clap::Arg::new("schema")
+                        .long("schema")
+                        .required(false)
+                        .value_name("FILE")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Where schema.json will be written, next to the data by default"),
                 ),
         )
         .subcommand(
             clap::Command::new("watch")
                 .about("Validates JSON to a schema, then runs the derive, sort, and plot scripts")
-                .arg(&config_arg),
+                .arg(&config_arg)
+                .arg(&dataset_arg),
         )
         .subcommand(
             clap::Command::new("migrate")
                 .about("Backup then update a JSON file and its corresponding schema")
-                .arg(&config_arg),
+                .arg(&config_arg)
+                .arg(&dataset_arg)
+                .arg(
+                    clap::Arg::new("steps")
+                        .long("steps")
+                        .required(false)
+                        .value_name("FILE")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("JSON migration steps to apply instead of the migrate script")
+                        .long_help(
+                            "JSON migration steps to apply instead of the migrate script. \
+Steps rename, delete, move, convert or set defaults for fields, \
+and a script step calls the migrate script for anything else. \
+Its schema can be generated with heda make-migration-schema.",
+                        ),
+                )
+                .arg(
+                    clap::Arg::new("dry-run")
+                        .long("dry-run")
+                        .required(false)
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Report what would change without writing or backing up anything"),
+                )
+                .arg(
+                    clap::Arg::new("undo")
+                        .long("undo")
+                        .required(false)
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["steps", "dry-run"])
+                        .help("Restore data, schema and type files from before the last migration"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("check")
+                .about("Checks the config, data, schema, type, and scripts files, for use in CI")
+                .arg(&config_arg)
+                .arg(&dataset_arg),
+        )
+        .subcommand(
+            clap::Command::new("diff")
+                .about("Compare records of two versions of the data, matched by primary key")
+                .long_about(
+                    "Compare records of two versions of the data, matched by primary key, \
+or by position without one. \
+Each version is a data file, a snapshot name from heda restore --list, \
+or a git revision like HEAD~1, which shows the data file at that revision. \
+A revision with a path, like main:people/data.json, is shown as is.",
+                )
+                .arg(
+                    clap::Arg::new("old")
+                        .required(true)
+                        .value_name("OLD")
+                        .help("The version to compare from"),
+                )
+                .arg(
+                    clap::Arg::new("new")
+                        .required(false)
+                        .value_name("NEW")
+                        .help("The version to compare to, the current data file if omitted"),
+                )
+                // OLD is required, so the config can't come before it as a positional
+                .arg(config_arg.clone().long("config"))
+                .arg(&dataset_arg)
+                .arg(
+                    clap::Arg::new("format")
+                        .long("format")
+                        .required(false)
+                        .value_name("FORMAT")
+                        .value_parser(subcmd::diff::FORMATS)
+                        .default_value("text")
+                        .help("Styled text, JSON for tools, or Markdown for reviews"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("merge-driver")
+                .about("Merge three versions of the data record by record, as a git merge driver")
+                .long_about(
+                    "Merge three versions of the data record by record, as a git merge driver. \
+Records are matched by primary key, and fields changed on only one side are merged. \
+Fields changed differently on both sides are replaced by an object \
+with a \"$conflict\" key holding each side, which heda check reports. \
+To use it, add \"data.json merge=heda\" to .gitattributes, and run \
+git config merge.heda.driver \"heda merge-driver %O %A %B %P\"",
+                )
+                .arg(
+                    clap::Arg::new("base")
+                        .required(true)
+                        .value_name("BASE")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("The common ancestor of both versions"),
+                )
+                .arg(
+                    clap::Arg::new("ours")
+                        .required(true)
+                        .value_name("OURS")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Our version, which the merge is written to"),
+                )
+                .arg(
+                    clap::Arg::new("theirs")
+                        .required(true)
+                        .value_name("THEIRS")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Their version"),
+                )
+                .arg(
+                    clap::Arg::new("path")
+                        .required(false)
+                        .value_name("PATH")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Where the data is in the repository, to pick its dataset"),
+                )
+                // BASE is required, so the config can't come before it as a positional
+                .arg(config_arg.clone().long("config"))
+                .arg(&dataset_arg),
+        )
+        .subcommand(
+            clap::Command::new("import")
+                .about("Backup then add records from another format to the data, checked by the schema")
+                .subcommand_required(true)
+                .subcommand(
+                    clap::Command::new("csv")
+                        .about("Add rows of a CSV file as records, reading cells as the schema types them")
+                        .long_about(
+                            "Add rows of a CSV file as records, reading cells as the schema types them. \
+Each column fills the field of the same name, with nested fields written like address.city. \
+Arrays are written as JSON or separated by semicolons, and empty cells leave fields missing. \
+Nothing is written if any row fails to read or fails the schema.",
+                        )
+                        .arg(
+                            clap::Arg::new("file")
+                                .required(true)
+                                .value_name("FILE")
+                                .value_hint(clap::ValueHint::FilePath)
+                                .help("CSV file with a header row"),
+                        )
+                        // FILE is required, so the config can't come before it as a positional
+                        .arg(config_arg.clone().long("config"))
+                        .arg(&dataset_arg)
+                        .arg(
+                            clap::Arg::new("map")
+                                .long("map")
+                                .required(false)
+                                .value_name("COLUMN=FIELD")
+                                .action(clap::ArgAction::Append)
+                                .help("Fill a field from a column with a different name, like \"Full Name=name\""),
+                        )
+                        .arg(
+                            clap::Arg::new("upsert")
+                                .long("upsert")
+                                .required(false)
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Update records with the same primary key instead of appending"),
+                        ),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("export")
+                .about("Write the data in another format")
+                .subcommand_required(true)
+                .subcommand(
+                    clap::Command::new("csv")
+                        .about("Write records as CSV rows, with nested fields flattened into columns")
+                        .arg(&config_arg)
+                        .arg(&dataset_arg)
+                        .arg(
+                            clap::Arg::new("output")
+                                .long("output")
+                                .required(false)
+                                .value_name("FILE")
+                                .value_hint(clap::ValueHint::FilePath)
+                                .help("Where the CSV is written, instead of stdout"),
+                        )
+                        .arg(
+                            clap::Arg::new("columns")
+                                .long("columns")
+                                .required(false)
+                                .value_name("PATHS")
+                                .help("Comma separated fields to write, like name,address.city, instead of every field"),
+                        ),
+                )
+                .subcommand(
+                    clap::Command::new("sqlite")
+                        .about("Write datasets into a SQLite database, with arrays in child tables")
+                        .long_about(
+                            "Write datasets into a SQLite database, with a table per dataset made from its schema. \
+Nested fields are flattened into columns like address_city, and arrays become child tables \
+whose _parent_id references the _id of their record. \
+The database is replaced on every export, so it never holds stale rows.",
+                        )
+                        .arg(&config_arg)
+                        .arg(&dataset_arg)
+                        .arg(
+                            clap::Arg::new("output")
+                                .long("output")
+                                .required(true)
+                                .value_name("FILE")
+                                .value_hint(clap::ValueHint::FilePath)
+                                .help("Where the database is written, replacing any there"),
+                        )
+                        .arg(
+                            clap::Arg::new("derived")
+                                .long("derived")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Also write what derive returns, as tables named like DATASET_derived"),
+                        ),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("dedupe")
+                .about("Backup then remove records repeating the primary key or a unique key")
+                .arg(&config_arg)
+                .arg(&dataset_arg)
+                .arg(
+                    clap::Arg::new("strategy")
+                        .long("strategy")
+                        .required(false)
+                        .value_name("STRATEGY")
+                        .value_parser(subcmd::dedupe::STRATEGIES)
+                        .help("How to resolve every group of duplicates, instead of prompting")
+                        .long_help(
+                            "How to resolve every group of duplicates, instead of prompting. \
+first keeps the earliest record, latest keeps the last one, \
+and merge starts from the first record, applying every non-null field of the later ones.",
+                        ),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("restore")
+                .about("Restore every project file from a snapshot in the backups directory")
+                .long_about(
+                    "Restore every project file from a snapshot in the backups directory. \
+Snapshots are made before heda migrate, dedupe and restore change anything, \
+and list the config, data, schema, type and scripts files by hash, \
+along with the heda version that made them. \
+File contents are stored compressed in the blobs directory, once for every snapshot with them.",
+                )
+                .arg(&config_arg)
+                .arg(
+                    clap::Arg::new("snapshot")
+                        .long("snapshot")
+                        .required(false)
+                        .value_name("NAME")
+                        .help("Snapshot to restore, by name or path, instead of the most recent"),
+                )
+                .arg(
+                    clap::Arg::new("list")
+                        .long("list")
+                        .required(false)
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("snapshot")
+                        .help("List snapshots instead of restoring one"),
+                )
+                .arg(
+                    clap::Arg::new("verify")
+                        .long("verify")
+                        .required(false)
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["snapshot", "list"])
+                        .help("Check every snapshot for missing or corrupted files instead of restoring one"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("test")
+                .about("Runs the test_* functions in the scripts file and its sibling tests file")
+                .arg(&config_arg)
+                .arg(&dataset_arg),
+        )
+        .subcommand(
+            clap::Command::new("repl")
+                .about("Evaluates rhai interactively, with the data and scripts loaded")
+                .arg(&config_arg)
+                .arg(&dataset_arg),
+        )
+        .subcommand(
+            clap::Command::new("generate-types")
+                .about("Generates rust structs or TypeScript declarations from schema.json")
+                .arg(&config_arg)
+                .arg(&dataset_arg)
+                .arg(
+                    clap::Arg::new("rust")
+                        .long("rust")
+                        .required(false)
+                        .value_name("FILE")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Where rust structs will be written, like the type.rs file"),
+                )
+                .arg(
+                    clap::Arg::new("ts")
+                        .long("ts")
+                        .required(false)
+                        .value_name("FILE")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Where TypeScript declarations will be written, like type.d.ts"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("make-migration-schema")
+                .about("Generate JSON schema for heda migration steps at the specified path")
+                .arg(
+                    clap::Arg::new("path")
+                        .required(true)
+                        .value_name("PATH")
+                        .value_hint(clap::ValueHint::DirPath)
+                        .help("Where the schema will be written"),
+                ),
         )
         .subcommand(
             clap::Command::new("make-config-schema")
@@ -86,19 +446,157 @@ fn main() {
         )
         .get_matches();
 
+    let debug = app_m.get_flag("debug");
     match app_m.subcommand() {
         Some(("init", sub_m)) => {
             let path_arg = sub_m.get_one::<String>("path").unwrap();
             let path = Path::new(path_arg);
-            subcmd::init::run_init(path);
+            let from_path = sub_m.get_one::<String>("from").map(Path::new);
+            subcmd::init::run_init(path, from_path);
+        }
+        Some(("infer", sub_m)) => {
+            let data_path = Path::new(sub_m.get_one::<String>("data").unwrap());
+            let data_dir = data_path.parent().unwrap_or(Path::new(""));
+            let type_path = match sub_m.get_one::<String>("type") {
+                Some(type_arg) => Path::new(type_arg).to_path_buf(),
+                None => data_dir.join("type.rs"),
+            };
+            let schema_path = match sub_m.get_one::<String>("schema") {
+                Some(schema_arg) => Path::new(schema_arg).to_path_buf(),
+                None => data_dir.join("schema.json"),
+            };
+            subcmd::infer::run_infer(data_path, &type_path, &schema_path);
         }
         Some(("watch", sub_m)) => {
-            let path_arg = sub_m.get_one::<String>("config").unwrap();
-            subcmd::watch::run_watch(Path::new(path_arg));
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            let dataset_arg = sub_m.get_one::<String>("dataset");
+            subcmd::watch::run_watch(&config_path, dataset_arg, debug);
         }
         Some(("migrate", sub_m)) => {
-            let path_arg = sub_m.get_one::<String>("config").unwrap();
-            subcmd::migrate::run_migrate(Path::new(path_arg));
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            let dataset_arg = sub_m.get_one::<String>("dataset");
+            let steps_path = sub_m.get_one::<String>("steps").map(Path::new);
+            let dry_run = sub_m.get_flag("dry-run");
+            if sub_m.get_flag("undo") {
+                subcmd::migrate::run_undo(&config_path, dataset_arg);
+            } else {
+                subcmd::migrate::run_migrate(&config_path, dataset_arg, steps_path, dry_run, debug);
+            }
+        }
+        Some(("check", sub_m)) => {
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            let dataset_arg = sub_m.get_one::<String>("dataset");
+            subcmd::check::run_check(&config_path, dataset_arg, debug);
+        }
+        Some(("diff", sub_m)) => {
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            let dataset_arg = sub_m.get_one::<String>("dataset");
+            let old_spec = sub_m.get_one::<String>("old").unwrap();
+            let new_spec = sub_m.get_one::<String>("new");
+            let format = sub_m.get_one::<String>("format").unwrap();
+            subcmd::diff::run_diff(&config_path, dataset_arg, old_spec, new_spec, format);
+        }
+        Some(("merge-driver", sub_m)) => {
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            let dataset_arg = sub_m.get_one::<String>("dataset");
+            let get_path = |name: &str| sub_m.get_one::<String>(name).map(Path::new);
+            // unwraps are safe, since clap requires the three versions
+            subcmd::merge_driver::run_merge_driver(
+                &config_path,
+                dataset_arg,
+                get_path("base").unwrap(),
+                get_path("ours").unwrap(),
+                get_path("theirs").unwrap(),
+                get_path("path"),
+            );
+        }
+        Some(("import", sub_m)) => match sub_m.subcommand() {
+            Some(("csv", format_m)) => {
+                let config_path = find_config_path(format_m.get_one::<String>("config"));
+                let dataset_arg = format_m.get_one::<String>("dataset");
+                let csv_path = Path::new(format_m.get_one::<String>("file").unwrap());
+                let map_args: Vec<String> = format_m
+                    .get_many::<String>("map")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect();
+                let upsert = format_m.get_flag("upsert");
+                subcmd::import::run_import_csv(
+                    &config_path,
+                    dataset_arg,
+                    csv_path,
+                    &map_args,
+                    upsert,
+                );
+            }
+            _ => println!("Unknown format. Try heda import --help"),
+        },
+        Some(("export", sub_m)) => match sub_m.subcommand() {
+            Some(("csv", format_m)) => {
+                let config_path = find_config_path(format_m.get_one::<String>("config"));
+                let dataset_arg = format_m.get_one::<String>("dataset");
+                let output_path = format_m.get_one::<String>("output").map(Path::new);
+                let columns_arg = format_m.get_one::<String>("columns");
+                subcmd::export::run_export_csv(&config_path, dataset_arg, output_path, columns_arg);
+            }
+            Some(("sqlite", format_m)) => {
+                let config_path = find_config_path(format_m.get_one::<String>("config"));
+                let dataset_arg = format_m.get_one::<String>("dataset");
+                // unwrap is safe, since output is required
+                let output_path = Path::new(format_m.get_one::<String>("output").unwrap());
+                let derived = format_m.get_flag("derived");
+                subcmd::export::run_export_sqlite(
+                    &config_path,
+                    dataset_arg,
+                    output_path,
+                    derived,
+                    debug,
+                );
+            }
+            _ => println!("Unknown format. Try heda export --help"),
+        },
+        Some(("dedupe", sub_m)) => {
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            let dataset_arg = sub_m.get_one::<String>("dataset");
+            let strategy_arg = sub_m.get_one::<String>("strategy");
+            subcmd::dedupe::run_dedupe(&config_path, dataset_arg, strategy_arg);
+        }
+        Some(("restore", sub_m)) => {
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            if sub_m.get_flag("list") {
+                subcmd::restore::run_list_snapshots(&config_path);
+            } else if sub_m.get_flag("verify") {
+                subcmd::restore::run_verify(&config_path);
+            } else {
+                let snapshot_arg = sub_m.get_one::<String>("snapshot");
+                subcmd::restore::run_restore(&config_path, snapshot_arg);
+            }
+        }
+        Some(("test", sub_m)) => {
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            let dataset_arg = sub_m.get_one::<String>("dataset");
+            subcmd::test::run_test(&config_path, dataset_arg, debug);
+        }
+        Some(("repl", sub_m)) => {
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            let dataset_arg = sub_m.get_one::<String>("dataset");
+            subcmd::repl::run_repl(&config_path, dataset_arg, debug);
+        }
+        Some(("generate-types", sub_m)) => {
+            let config_path = find_config_path(sub_m.get_one::<String>("config"));
+            let dataset_arg = sub_m.get_one::<String>("dataset");
+            let rust_path = sub_m.get_one::<String>("rust").map(Path::new);
+            let typescript_path = sub_m.get_one::<String>("ts").map(Path::new);
+            subcmd::generate_types::run_generate_types(
+                &config_path,
+                dataset_arg,
+                rust_path,
+                typescript_path,
+            );
+        }
+        Some(("make-migration-schema", sub_m)) => {
+            let path_arg = sub_m.get_one::<String>("path").unwrap();
+            subcmd::make_migration_schema::make_migration_schema(Path::new(path_arg));
         }
         Some(("make-config-schema", sub_m)) => {
             let path_arg = sub_m.get_one::<String>("path").unwrap();
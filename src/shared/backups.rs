@@ -1,10 +1,14 @@
 use std::path::Path;
 
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
 
 use crate::shared::path::style_path;
 
-use super::errors::RunningErrors;
+use super::{
+    errors::RunningErrors,
+    journal::JOURNAL_FILE_NAME,
+    snapshots::{get_unused_blobs, BLOBS_DIRECTORY_NAME},
+};
 
 const BACKUP_TIME_FMT: &str = "%Y-%m-%dT%H:%M:%S";
 const BACKUP_SPLIT_TOKEN: &str = "_backup_";
@@ -16,20 +20,38 @@ pub fn get_backup_path(path: &Path) -> Box<Path> {
         .to_str()
         .expect(format!("{} file stem should be UTF-8", style_path(path, "backup")).as_str());
 
-    let file_extension = path
-        .extension()
-        .expect(format!("{} file should have extension", style_path(path, "backup")).as_str());
-
     let now_date: DateTime<chrono::Local> = chrono::Local::now();
     let now_str = now_date.format(BACKUP_TIME_FMT).to_string();
     let backup_stem = format!("{file_stem}{BACKUP_SPLIT_TOKEN}{now_str}");
+    let backup_name =
+        Path::new(backup_stem.as_str()).with_extension(path.extension().unwrap_or_default());
 
     // return with cur dir so it can be used with std::path::Path.join
     // without it, join will ignore the left if the right appears absolute
-    Box::from(
-        Path::new(std::path::Component::CurDir.as_os_str())
-            .join(Path::new(backup_stem.as_str()).with_extension(file_extension)),
-    )
+    Box::from(Path::new(std::path::Component::CurDir.as_os_str()).join(backup_name))
+}
+
+// a backup path in the backups directory that isn't taken yet, creating the directory
+pub fn get_free_backup_path(path: &Path, backups_directory: &Path) -> Box<Path> {
+    std::fs::create_dir_all(backups_directory).expect(
+        format!(
+            "Should create {}",
+            style_path(backups_directory, "backups directory")
+        )
+        .as_str(),
+    );
+    let mut backup_path: Box<Path> = Box::from(backups_directory.join(get_backup_path(path)));
+    // backups within the same second get a counter
+    let mut counter = 1;
+    while backup_path.exists() {
+        let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let numbered_path = path
+            .with_file_name(format!("{file_stem}-{counter}"))
+            .with_extension(path.extension().unwrap_or_default());
+        backup_path = Box::from(backups_directory.join(get_backup_path(&numbered_path)));
+        counter += 1;
+    }
+    backup_path
 }
 
 pub fn get_backup_timestamp(path: &Path) -> DateTime<FixedOffset> {
@@ -48,14 +70,19 @@ pub fn get_backup_timestamp(path: &Path) -> DateTime<FixedOffset> {
             )
             .as_str(),
         );
-    DateTime::parse_from_str(time_str, BACKUP_TIME_FMT).expect(
-        format!(
-            "{} name should contain timestamp with format {}",
-            style_path(&path, "backup"),
-            BACKUP_TIME_FMT
+    // names are written in local time without an offset, so it's added back on reading
+    NaiveDateTime::parse_from_str(time_str, BACKUP_TIME_FMT)
+        .ok()
+        .and_then(|naive_date| naive_date.and_local_timezone(chrono::Local).earliest())
+        .expect(
+            format!(
+                "{} name should contain timestamp with format {}",
+                style_path(&path, "backup"),
+                BACKUP_TIME_FMT
+            )
+            .as_str(),
         )
-        .as_str(),
-    )
+        .fixed_offset()
 }
 
 pub fn remove_old_backups(
@@ -70,6 +97,7 @@ pub fn remove_old_backups(
         .as_str(),
     );
 
+    let mut running_errors = RunningErrors::new();
     for entry_res in backups {
         let entry = entry_res
             .expect(
@@ -80,9 +108,15 @@ pub fn remove_old_backups(
                 .as_str(),
             )
             .path();
+        // the migration journal and blobs live alongside the snapshots referring to them
+        if [JOURNAL_FILE_NAME, BLOBS_DIRECTORY_NAME]
+            .iter()
+            .any(|name| entry.file_name() == Some(std::ffi::OsStr::new(name)))
+        {
+            continue;
+        }
         let timestamp = get_backup_timestamp(&entry);
 
-        let mut running_errors = RunningErrors::new();
         if timestamp < remove_older_than {
             let rm_res = std::fs::remove_file(&entry);
             if let Err(rm_err) = rm_res {
@@ -93,4 +127,34 @@ pub fn remove_old_backups(
             }
         }
     }
+    running_errors.print_errs();
+    // blobs are shared, so are only removed once no remaining snapshot needs them
+    for blob_path in get_unused_blobs(backups_path) {
+        let _ = std::fs::remove_file(&blob_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_are_read_back_from_backup_names() {
+        let backups_directory =
+            std::env::temp_dir().join(format!("heda_backups_test_{}", std::process::id()));
+        let before = chrono::Local::now().fixed_offset() - chrono::Duration::seconds(1);
+        let backup_path = get_free_backup_path(Path::new("data.json"), &backups_directory);
+        std::fs::write(&backup_path, "[]").unwrap();
+        // numbered when taken within the same second
+        let numbered_path = get_free_backup_path(Path::new("data.json"), &backups_directory);
+        let after = chrono::Local::now().fixed_offset();
+        std::fs::remove_dir_all(&backups_directory).unwrap();
+
+        assert_ne!(backup_path, numbered_path);
+        for path in [&backup_path, &numbered_path] {
+            assert_eq!(path.extension(), Some(std::ffi::OsStr::new("json")));
+            let timestamp = get_backup_timestamp(path);
+            assert!(before <= timestamp && timestamp <= after);
+        }
+    }
 }
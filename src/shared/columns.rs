@@ -0,0 +1,251 @@
+use serde_json::Value;
+
+use super::type_rs::{TypeDef, TypeExpr, TypeFile};
+
+// separates items of an array within one cell, unless they are written as JSON
+const ITEM_SEPARATOR: char = ';';
+
+// a leaf field of the schema, as a column of a flat table
+pub struct Column {
+    // dotted path through nested structs, like address.city
+    pub path: String,
+    pub ty: TypeExpr,
+}
+
+// nested structs are flattened, and anything else is a single column
+pub fn get_columns(type_file: &TypeFile) -> Vec<Column> {
+    get_struct_columns(type_file, &type_file.root, &[])
+}
+
+// structs in ancestors are kept as JSON rather than flattened again
+pub fn get_struct_columns(
+    type_file: &TypeFile,
+    struct_name: &str,
+    ancestors: &[String],
+) -> Vec<Column> {
+    let mut columns: Vec<Column> = Vec::new();
+    let mut visiting: Vec<String> = ancestors.to_vec();
+    push_columns(type_file, struct_name, "", &mut visiting, &mut columns);
+    columns
+}
+
+fn push_columns(
+    type_file: &TypeFile,
+    struct_name: &str,
+    prefix: &str,
+    visiting: &mut Vec<String>,
+    columns: &mut Vec<Column>,
+) {
+    let Some(TypeDef::Struct(struct_def)) =
+        type_file.defs.iter().find(|def| def.name() == struct_name)
+    else {
+        return;
+    };
+    visiting.push(struct_name.to_string());
+    for field in &struct_def.fields {
+        let path = format!("{prefix}{}", field.name);
+        let inner_ty = match &field.ty {
+            TypeExpr::Option(inner) => inner.as_ref(),
+            ty => ty,
+        };
+        // structs that contain themselves can't be flattened, so are kept as JSON
+        let nested_name = match inner_ty {
+            TypeExpr::Named(name)
+                if !visiting.contains(name)
+                    && type_file
+                        .defs
+                        .iter()
+                        .any(|def| matches!(def, TypeDef::Struct(_)) && def.name() == name) =>
+            {
+                Some(name)
+            }
+            _ => None,
+        };
+        match nested_name {
+            Some(name) => push_columns(type_file, name, &format!("{path}."), visiting, columns),
+            None => columns.push(Column {
+                path,
+                ty: field.ty.clone(),
+            }),
+        }
+    }
+    visiting.pop();
+}
+
+// None for an empty cell, which leaves the field missing
+// strings that are empty or only whitespace are written as JSON, like "", to tell them apart
+pub fn read_cell(cell: &str, ty: &TypeExpr, type_file: &TypeFile) -> Result<Option<Value>, String> {
+    let trimmed = cell.trim();
+    if let (TypeExpr::Primitive(name), Ok(Value::String(str))) = (ty, serde_json::from_str(trimmed))
+    {
+        if name == "String" && str.trim().is_empty() {
+            return Ok(Some(Value::String(str)));
+        }
+    }
+    if trimmed.is_empty() {
+        // an empty list is still a list, unless it's optional
+        return Ok(match ty {
+            TypeExpr::Vec(_) | TypeExpr::Set(_) => Some(Value::Array(Vec::new())),
+            _ => None,
+        });
+    }
+    let fail = |expected: &str| format!("Could not read {trimmed:?} as {expected}");
+    let value = match ty {
+        TypeExpr::Option(inner) => return read_cell(cell, inner, type_file),
+        TypeExpr::Primitive(name) if name == "String" => Value::String(cell.to_string()),
+        TypeExpr::Primitive(name) if name == "char" => match trimmed.chars().count() {
+            1 => Value::String(trimmed.to_string()),
+            _ => return Err(fail("a single character")),
+        },
+        TypeExpr::Primitive(name) if name == "bool" => match trimmed.to_lowercase().as_str() {
+            "true" | "yes" | "y" | "1" => Value::Bool(true),
+            "false" | "no" | "n" | "0" => Value::Bool(false),
+            _ => return Err(fail("a boolean")),
+        },
+        TypeExpr::Primitive(name) if name == "f32" || name == "f64" => {
+            let float = trimmed.parse::<f64>().map_err(|_| fail("a number"))?;
+            serde_json::Number::from_f64(float)
+                .map(Value::Number)
+                .ok_or(fail("a number"))?
+        }
+        TypeExpr::Primitive(name) if name.starts_with('u') => Value::from(
+            trimmed
+                .parse::<u64>()
+                .map_err(|_| fail(&format!("a {name}")))?,
+        ),
+        TypeExpr::Primitive(name) => Value::from(
+            trimmed
+                .parse::<i64>()
+                .map_err(|_| fail(&format!("an {name}")))?,
+        ),
+        // formats are left to the schema, which reports them with the other issues
+        TypeExpr::Date | TypeExpr::DateTime => Value::String(trimmed.to_string()),
+        TypeExpr::Vec(inner) | TypeExpr::Set(inner) => {
+            if trimmed.starts_with('[') {
+                serde_json::from_str(trimmed).map_err(|_| fail("a JSON array"))?
+            } else {
+                let mut items: Vec<Value> = Vec::new();
+                for item in trimmed.split(ITEM_SEPARATOR) {
+                    if let Some(item_value) = read_cell(item, inner, type_file)? {
+                        items.push(item_value);
+                    }
+                }
+                Value::Array(items)
+            }
+        }
+        TypeExpr::Map(_) => serde_json::from_str(trimmed).map_err(|_| fail("a JSON object"))?,
+        TypeExpr::Value => {
+            serde_json::from_str(trimmed).unwrap_or_else(|_| Value::String(cell.to_string()))
+        }
+        TypeExpr::Named(name) => match type_file.defs.iter().find(|def| def.name() == name) {
+            Some(TypeDef::Enum(enum_def)) => {
+                if !enum_def.variants.iter().any(|variant| variant == trimmed) {
+                    return Err(fail(&format!("one of {}", enum_def.variants.join(", "))));
+                }
+                Value::String(trimmed.to_string())
+            }
+            _ => serde_json::from_str(trimmed).map_err(|_| fail("a JSON object"))?,
+        },
+    };
+    Ok(Some(value))
+}
+
+// the inverse of read_cell, so exported tables can be imported again
+pub fn write_cell(value_opt: Option<&Value>) -> String {
+    let is_plain_item = |item: &Value| match item {
+        Value::String(str) => {
+            !str.contains(ITEM_SEPARATOR) && !str.starts_with('[') && !str.trim().is_empty()
+        }
+        Value::Number(_) | Value::Bool(_) => true,
+        _ => false,
+    };
+    match value_opt {
+        None | Some(Value::Null) => String::new(),
+        // an empty cell would leave the field missing
+        Some(Value::String(str)) if str.trim().is_empty() => Value::String(str.clone()).to_string(),
+        Some(Value::String(str)) => str.clone(),
+        Some(Value::Number(number)) => number.to_string(),
+        Some(Value::Bool(bool_value)) => bool_value.to_string(),
+        Some(Value::Array(items)) if !items.is_empty() && items.iter().all(is_plain_item) => items
+            .iter()
+            .map(|item| write_cell(Some(item)))
+            .collect::<Vec<String>>()
+            .join(&ITEM_SEPARATOR.to_string()),
+        Some(value) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const TYPE_RS: &str = r#"
+pub struct Datum {
+    pub size: Size,
+}
+
+pub enum Size {
+    Small,
+    Large,
+}
+"#;
+
+    fn parse_ty(ty_str: &str) -> TypeExpr {
+        let type_rs = format!("pub struct Datum {{ pub value: {ty_str}, }}");
+        let type_file = TypeFile::parse(&type_rs).unwrap();
+        get_columns(&type_file).remove(0).ty
+    }
+
+    // what importing an exported cell gives back
+    fn round_trip(value: Value, ty_str: &str) -> (String, Option<Value>) {
+        let type_file = TypeFile::parse(TYPE_RS).unwrap();
+        let cell = write_cell(Some(&value));
+        let read_value = read_cell(&cell, &parse_ty(ty_str), &type_file).unwrap();
+        (cell, read_value)
+    }
+
+    #[test]
+    fn cells_round_trip() {
+        for (value, ty_str) in [
+            (json!(["a", "b"]), "Vec<String>"),
+            (json!([1, 2]), "Vec<i64>"),
+            (json!(["a;b", "c"]), "Vec<String>"),
+            (json!(["[a]"]), "Vec<String>"),
+            (json!(["a", " "]), "Vec<String>"),
+            (json!([]), "Vec<String>"),
+            (json!("a;b"), "String"),
+            (json!("[a]"), "String"),
+            (json!(""), "String"),
+            (json!("  "), "String"),
+            (json!(""), "Option<String>"),
+            (json!("Small"), "Size"),
+            (json!(["Small", "Large"]), "Vec<Size>"),
+            (json!(true), "bool"),
+            (json!(-3), "i64"),
+            (json!(3), "u8"),
+            (json!(1.5), "f64"),
+            (json!("x"), "char"),
+        ] {
+            let (cell, read_value) = round_trip(value.clone(), ty_str);
+            assert_eq!(read_value, Some(value), "{ty_str} written as {cell:?}");
+        }
+    }
+
+    #[test]
+    fn cells_are_written_plainly_where_they_can_be() {
+        assert_eq!(round_trip(json!(["a", "b"]), "Vec<String>").0, "a;b");
+        assert_eq!(round_trip(json!(["a;b"]), "Vec<String>").0, r#"["a;b"]"#);
+        assert_eq!(round_trip(json!([]), "Vec<String>").0, "[]");
+        assert_eq!(round_trip(json!(""), "String").0, r#""""#);
+        assert_eq!(write_cell(None), "");
+        let type_file = TypeFile::parse(TYPE_RS).unwrap();
+        assert_eq!(
+            read_cell("", &parse_ty("Option<String>"), &type_file),
+            Ok(None)
+        );
+        assert!(read_cell("Medium", &parse_ty("Size"), &type_file).is_err());
+        assert!(read_cell("xy", &parse_ty("char"), &type_file).is_err());
+    }
+}
@@ -1,50 +1,373 @@
-use std::path::Path;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-use super::json::validate_json;
+use super::{
+    data::{plan_data_files, try_read_data_files, DataWrite},
+    datum::Datum,
+    issues::{assert_no_errors, Issue},
+    json::{get_schema_issues, try_get_schema_issues, try_read_json, validate_json_with_schema},
+    path::{resolve_path, style_path},
+    schema::get_schema,
+    scripts::RhaiSpace,
+};
+
+const CONFIG_ENV: &str = "HEDA_CONFIG";
+const CONFIG_FILE_NAMES: [&str; 2] = ["heda.json", "config.json"];
 
 /// # heda config
 /// Paths to user defined files and directories for heda (Human Editable Data Assistant)
 #[allow(non_snake_case)]
 #[derive(serde::Deserialize, schemars::JsonSchema)]
 pub struct ConfigJsonSchema {
+    // paths may be absolute, start with ~, or be relative to the directory containing the config
     /// Path to a directory to store copies of the data before a migration is run.
     pub backupsDirectory: String,
-    /// Path to the file containing the data as a JSON array.
-    pub dataJson: String,
+    /// Path to the file containing the data as a JSON array, or as YAML, TOML ([[records]]) or NDJSON by its extension. Can be a directory or glob of such files, each holding one record or an array of them. YAML files lose their comments when heda writes them, and TOML files can't hold nulls in arrays. Required unless datasets is used.
+    pub dataJson: Option<String>,
+    /// Named datasets sharing the plots and backups directories, used instead of dataJson, schemaJson, and typeRs.
+    pub datasets: Option<BTreeMap<String, DatasetJsonSchema>>,
     /// Path to a directory to store outputs of plots.
     pub plotsDirectory: String,
+    /// Field uniquely identifying each item in the data. Not used with datasets, which each set their own.
+    pub primaryKey: Option<String>,
+    /// Path to the JSON Schema file for the data. File contents are managed automatically. Required unless datasets is used.
+    pub schemaJson: Option<String>,
+    /// Path to the .rhai file containing the rhai scripts for derivation, sorting, plotting, and migration. With datasets, optional scripts whose functions receive a map of every dataset name to its data.
+    pub scriptsRhai: Option<String>,
+    /// Path to the .rs file containing the struct typing an individual item in the JSON array. Required unless datasets is used.
+    pub typeRs: Option<String>,
+    /// Sets of fields, each of which should be unique across the data when taken together. Not used with datasets, which each set their own.
+    pub uniqueKeys: Option<Vec<Vec<String>>>,
+}
+
+/// # heda dataset
+/// Paths to user defined files for one collection in a heda workspace
+#[allow(non_snake_case)]
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetJsonSchema {
+    /// Path to the file containing the data as a JSON array, or as YAML, TOML ([[records]]) or NDJSON by its extension. Can be a directory or glob of such files, each holding one record or an array of them. YAML files lose their comments when heda writes them, and TOML files can't hold nulls in arrays.
+    pub dataJson: String,
+    /// Fields of each item referencing the primary key of another dataset, by the name of that dataset.
+    pub foreignKeys: Option<BTreeMap<String, String>>,
+    /// Field uniquely identifying each item in the data.
+    pub primaryKey: Option<String>,
     /// Path to the JSON Schema file for the data. File contents are managed automatically.
     pub schemaJson: String,
     /// Path to the .rhai file containing the rhai scripts for derivation, sorting, plotting, and migration.
     pub scriptsRhai: String,
     /// Path to the .rs file containing the struct typing an individual item in the JSON array.
     pub typeRs: String,
+    /// Sets of fields, each of which should be unique across the data when taken together.
+    pub uniqueKeys: Option<Vec<Vec<String>>>,
 }
 
-// rust parsing of the config JSON
+// rust parsing of the config JSON, for a single dataset
 pub struct Config {
+    // only set for datasets of a workspace
+    pub name: Option<String>,
+    pub root_directory: Box<Path>,
     pub backups_directory: Box<Path>,
     pub data_json: Box<Path>,
     pub plots_directory: Box<Path>,
     pub schema_json: Box<Path>,
     pub scripts_rhai: Box<Path>,
     pub type_rs: Box<Path>,
+    pub primary_key: Option<String>,
+    // field -> name of the dataset whose primary key it references
+    pub foreign_keys: BTreeMap<String, String>,
+    pub unique_keys: Vec<Vec<String>>,
 }
 
 impl Config {
+    // validated against schema.json, with issues named by dataset in workspaces
+    pub fn read_data(&self) -> Vec<Datum> {
+        self.read_data_with(None)
+    }
+
+    // also validated by the validate fns, so schema and script issues are reported together
+    pub fn read_validated_data(&self, rhai_space: &mut RhaiSpace) -> Vec<Datum> {
+        self.read_data_with(Some(rhai_space))
+    }
+
+    fn read_data_with(&self, rhai_space_opt: Option<&mut RhaiSpace>) -> Vec<Datum> {
+        let (data_json, data_files) =
+            try_read_data_files(&self.data_json).unwrap_or_else(|err| panic!("{err}"));
+        let mut issues: Vec<Issue> = get_schema_issues(&data_json, &self.schema_json);
+        // script rules still run on data failing the schema, as long as it has the right shape
+        let data_opt = serde_json::from_value::<Vec<Datum>>(data_json).ok();
+        if let (Some(rhai_space), Some(data)) = (rhai_space_opt, &data_opt) {
+            issues.extend(rhai_space.validate(data));
+        }
+        let issues: Vec<Issue> = issues
+            .into_iter()
+            .map(|issue| issue.in_dataset(&self.name).in_data_file(&data_files))
+            .collect();
+        assert_no_errors("Validation", &issues);
+        data_opt.expect(
+            format!(
+                "{} should parse into rust",
+                style_path(&self.data_json, "data")
+            )
+            .as_str(),
+        )
+    }
+
+    // in the format of the data, and for split data, back into the file of each record
+    // planned before the snapshot is made, so a write that can't be done fails before it starts
+    pub fn plan_write_data(&self, data: &[Datum]) -> Result<DataWrite, String> {
+        plan_data_files(&self.data_json, data, self.primary_key.as_deref())
+    }
+}
+
+// rust parsing of the config JSON, for every dataset in it
+pub struct Workspace {
+    pub config_path: Box<Path>,
+    pub root_directory: Box<Path>,
+    pub backups_directory: Box<Path>,
+    pub plots_directory: Box<Path>,
+    // scripts over every dataset at once, only for workspaces
+    pub scripts_rhai: Option<Box<Path>>,
+    pub datasets: Vec<Config>,
+}
+
+impl Workspace {
     pub fn new(config_path: &Path) -> Self {
-        let raw_json =
-            validate_json::<ConfigJsonSchema>(config_path, Path::new("./config-schema.json"));
-        let box_path = |base_path: &Path, path_str: &String| {
-            Box::from(base_path.join(path_str).as_path())
+        let raw_json = validate_json_with_schema::<ConfigJsonSchema>(
+            config_path,
+            &get_schema::<ConfigJsonSchema>(),
+        );
+        if let Some(err) = get_layout_err(&raw_json) {
+            panic!("{} {}", style_path(config_path, "config"), err);
+        }
+        // the directory containing the config, which paths in it are relative to
+        let root_directory: Box<Path> = match config_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => Box::from(parent),
+            _ => Box::from(Path::new(std::path::Component::CurDir.as_os_str())),
+        };
+        // environment overrides come from the shell, so are relative to the current directory
+        let current_dir = std::env::current_dir().expect("Should get current directory");
+        let get_path = |env_key: &str, path_str: &String| match std::env::var(env_key) {
+            Ok(env_str) if !env_str.is_empty() => resolve_path(&current_dir, &env_str),
+            _ => resolve_path(&root_directory, path_str),
         };
-        Config {
-            backups_directory: box_path(config_path, &raw_json.backupsDirectory),
-            data_json: box_path(config_path, &raw_json.dataJson),
-            plots_directory: box_path(config_path, &raw_json.plotsDirectory),
-            schema_json: box_path(config_path, &raw_json.schemaJson),
-            scripts_rhai: box_path(config_path, &raw_json.scriptsRhai),
-            type_rs: box_path(config_path, &raw_json.typeRs),
+
+        let backups_directory = get_path("HEDA_BACKUPS_DIRECTORY", &raw_json.backupsDirectory);
+        let plots_directory = get_path("HEDA_PLOTS_DIRECTORY", &raw_json.plotsDirectory);
+        let make_config = |name: Option<String>, dataset_json: DatasetJsonSchema| {
+            // datasets are overridden with their name, like HEDA_PEOPLE_DATA_JSON
+            let env_prefix = match &name {
+                Some(name) => format!("HEDA_{}_", to_env_case(name)),
+                None => "HEDA_".to_string(),
+            };
+            let get_dataset_path = |env_key: &str, path_str: &String| {
+                get_path(&format!("{env_prefix}{env_key}"), path_str)
+            };
+            Config {
+                data_json: get_dataset_path("DATA_JSON", &dataset_json.dataJson),
+                schema_json: get_dataset_path("SCHEMA_JSON", &dataset_json.schemaJson),
+                scripts_rhai: get_dataset_path("SCRIPTS_RHAI", &dataset_json.scriptsRhai),
+                type_rs: get_dataset_path("TYPE_RS", &dataset_json.typeRs),
+                primary_key: dataset_json.primaryKey,
+                foreign_keys: dataset_json.foreignKeys.unwrap_or_default(),
+                unique_keys: dataset_json.uniqueKeys.unwrap_or_default(),
+                name,
+                root_directory: root_directory.clone(),
+                backups_directory: backups_directory.clone(),
+                plots_directory: plots_directory.clone(),
+            }
+        };
+
+        // unwraps are safe, since get_layout_err ensures one layout is fully specified
+        let (scripts_rhai, datasets) = match raw_json.datasets {
+            Some(datasets_json) => (
+                raw_json
+                    .scriptsRhai
+                    .map(|scripts_str| get_path("HEDA_SCRIPTS_RHAI", &scripts_str)),
+                datasets_json
+                    .into_iter()
+                    .map(|(name, dataset_json)| make_config(Some(name), dataset_json))
+                    .collect(),
+            ),
+            None => (
+                None,
+                vec![make_config(
+                    None,
+                    DatasetJsonSchema {
+                        dataJson: raw_json.dataJson.unwrap(),
+                        foreignKeys: None,
+                        primaryKey: raw_json.primaryKey,
+                        schemaJson: raw_json.schemaJson.unwrap(),
+                        scriptsRhai: raw_json.scriptsRhai.unwrap(),
+                        typeRs: raw_json.typeRs.unwrap(),
+                        uniqueKeys: raw_json.uniqueKeys,
+                    },
+                )],
+            ),
+        };
+        Workspace {
+            config_path: Box::from(config_path),
+            root_directory,
+            backups_directory,
+            plots_directory,
+            scripts_rhai,
+            datasets,
+        }
+    }
+
+    pub fn is_workspace(&self) -> bool {
+        self.datasets.iter().any(|config| config.name.is_some())
+    }
+
+    // every dataset, unless one is picked with --dataset
+    pub fn select(&self, dataset_arg: Option<&String>) -> Vec<&Config> {
+        match dataset_arg {
+            None => self.datasets.iter().collect(),
+            Some(dataset_name) => {
+                let config = self
+                    .datasets
+                    .iter()
+                    .find(|config| config.name.as_ref() == Some(dataset_name));
+                match config {
+                    Some(config) => vec![config],
+                    None => panic!(
+                        "Should find dataset {} in config, which has: {}",
+                        console::style(dataset_name).bold(),
+                        self.datasets
+                            .iter()
+                            .filter_map(|config| config.name.clone())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ),
+                }
+            }
+        }
+    }
+}
+
+fn to_env_case(name: &str) -> String {
+    name.chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// JSON schema can't neatly say "either these fields or datasets", so it's checked here
+fn get_layout_err(raw_json: &ConfigJsonSchema) -> Option<String> {
+    let single_fields = [
+        ("dataJson", &raw_json.dataJson),
+        ("schemaJson", &raw_json.schemaJson),
+        ("scriptsRhai", &raw_json.scriptsRhai),
+        ("typeRs", &raw_json.typeRs),
+    ];
+    match &raw_json.datasets {
+        Some(datasets) => {
+            let set_fields: Vec<&str> = single_fields
+                .iter()
+                .filter(|(name, value)| *name != "scriptsRhai" && value.is_some())
+                .map(|(name, _)| *name)
+                .collect();
+            if !set_fields.is_empty() {
+                return Some(format!(
+                    "should not set {} alongside datasets; set them for each dataset instead",
+                    set_fields.join(", ")
+                ));
+            }
+            if raw_json.primaryKey.is_some() || raw_json.uniqueKeys.is_some() {
+                return Some(
+                    "should not set primaryKey or uniqueKeys alongside datasets; set them for each dataset instead"
+                        .to_string(),
+                );
+            }
+            if datasets.is_empty() {
+                return Some("should have at least one dataset".to_string());
+            }
+            // foreign keys can only be checked against a dataset with a primary key
+            for (name, dataset) in datasets {
+                for (field, target) in dataset.foreignKeys.iter().flatten() {
+                    match datasets.get(target) {
+                        Some(target_dataset) if target_dataset.primaryKey.is_some() => {}
+                        Some(_) => {
+                            return Some(format!(
+                                "dataset {name} has foreign key {field} to dataset {target}, which should set primaryKey"
+                            ))
+                        }
+                        None => {
+                            return Some(format!(
+                                "dataset {name} has foreign key {field} to dataset {target}, which should exist"
+                            ))
+                        }
+                    }
+                }
+            }
+            None
+        }
+        None => {
+            let missing_fields: Vec<&str> = single_fields
+                .iter()
+                .filter(|(_, value)| value.is_none())
+                .map(|(name, _)| *name)
+                .collect();
+            if missing_fields.is_empty() {
+                None
+            } else {
+                Some(format!(
+                    "should set {}, or use datasets instead",
+                    missing_fields.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+// for reporting every problem with the config, instead of panicking like Workspace::new
+pub fn get_config_issues(config_path: &Path) -> Result<Vec<Issue>, String> {
+    let config_json = try_read_json(config_path)?;
+    // unwrap is safe, since schemars generates a valid schema
+    let mut issues =
+        try_get_schema_issues(&config_json, &get_schema::<ConfigJsonSchema>()).unwrap();
+    if issues.is_empty() {
+        // unwrap is safe, since the JSON matches the schema of the struct
+        let raw_json = serde_json::from_value::<ConfigJsonSchema>(config_json).unwrap();
+        if let Some(err) = get_layout_err(&raw_json) {
+            issues.push(Issue::error(None, None, format!("Config {err}")));
+        }
+    }
+    Ok(issues)
+}
+
+// an explicit path wins, then HEDA_CONFIG, then searching upwards like git does
+pub fn find_config_path(path_arg: Option<&String>) -> PathBuf {
+    if let Some(path_str) = path_arg {
+        return PathBuf::from(path_str);
+    }
+    if let Ok(env_str) = std::env::var(CONFIG_ENV) {
+        if !env_str.is_empty() {
+            return PathBuf::from(env_str);
+        }
+    }
+
+    let current_dir = std::env::current_dir().expect("Should get current directory");
+    for dir in current_dir.ancestors() {
+        for file_name in CONFIG_FILE_NAMES {
+            let candidate = dir.join(file_name);
+            if candidate.is_file() {
+                return candidate;
+            }
         }
     }
+    panic!(
+        "Should find {} or {} in {} or its parents, or have {} set",
+        console::style(CONFIG_FILE_NAMES[0]).cyan(),
+        console::style(CONFIG_FILE_NAMES[1]).cyan(),
+        console::style(current_dir.to_string_lossy()).blue(),
+        console::style(CONFIG_ENV).bold()
+    );
 }
@@ -0,0 +1,620 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use super::{datum::Datum, path::style_path};
+
+// a TOML document is a table, so records are an array of tables under this key
+const TOML_KEY: &str = "records";
+// files of a data directory, which can mix formats
+const DATA_EXTENSIONS: [&str; 6] = ["json", "yaml", "yml", "toml", "ndjson", "jsonl"];
+// for records added to a directory that doesn't have any files yet
+const DEFAULT_EXTENSION: &str = "json";
+
+// the format of a data file, by its extension, defaulting to JSON
+#[derive(Clone, Copy, PartialEq)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+    // one record per line, for large logs only ever appended to
+    Ndjson,
+}
+
+impl DataFormat {
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("yaml" | "yml") => DataFormat::Yaml,
+            Some("toml") => DataFormat::Toml,
+            Some("ndjson" | "jsonl") => DataFormat::Ndjson,
+            _ => DataFormat::Json,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataFormat::Json => "JSON",
+            DataFormat::Yaml => "YAML",
+            DataFormat::Toml => "TOML",
+            DataFormat::Ndjson => "NDJSON",
+        }
+    }
+
+    // errors keep the line and column of the original format, since that's what gets edited
+    pub fn parse(&self, str: &str) -> Result<Value, String> {
+        match self {
+            DataFormat::Json => serde_json::from_str(str).map_err(|err| err.to_string()),
+            DataFormat::Yaml => serde_yaml::from_str(str).map_err(|err| err.to_string()),
+            DataFormat::Toml => {
+                let table = str.parse::<toml::Table>().map_err(|err| err.to_string())?;
+                if let Some(key) = table.keys().find(|key| *key != TOML_KEY) {
+                    return Err(format!(
+                        "{key} should not be a top level key, since records go in [[{TOML_KEY}]]"
+                    ));
+                }
+                Ok(match table.get(TOML_KEY) {
+                    Some(records) => from_toml_value(records),
+                    // a document without any [[records]] has no records
+                    None => Value::Array(Vec::new()),
+                })
+            }
+            DataFormat::Ndjson => {
+                let mut records: Vec<Value> = Vec::new();
+                for (line_index, line) in str.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    // serde_json counts lines within the one record, which is always line 1
+                    let record = serde_json::from_str(line).map_err(|err| {
+                        let err_str = err.to_string();
+                        let position_suffix =
+                            format!(" at line {} column {}", err.line(), err.column());
+                        format!(
+                            "{} at line {} column {}",
+                            err_str.strip_suffix(&position_suffix).unwrap_or(&err_str),
+                            line_index + 1,
+                            err.column()
+                        )
+                    })?;
+                    records.push(record);
+                }
+                Ok(Value::Array(records))
+            }
+        }
+    }
+
+    pub fn to_string(&self, data: &Value) -> Result<String, String> {
+        match self {
+            DataFormat::Json => serde_json::to_string_pretty(data).map_err(|err| err.to_string()),
+            DataFormat::Yaml => serde_yaml::to_string(data).map_err(|err| err.to_string()),
+            DataFormat::Toml => {
+                // TOML has no null, and a missing field reads the same as a null one
+                // but a null in an array has nothing to stand for it
+                if let Some(pointer) = find_null_in_array(data, "") {
+                    return Err(format!(
+                        "TOML has no null, so it can't be in an array, like at {pointer}"
+                    ));
+                }
+                let mut document = serde_json::Map::new();
+                document.insert(TOML_KEY.to_string(), without_nulls(data));
+                toml::to_string(&document).map_err(|err| err.to_string())
+            }
+            DataFormat::Ndjson => {
+                let records = data.as_array().ok_or("data should be an array")?;
+                let mut str = String::new();
+                for record in records {
+                    str.push_str(&serde_json::to_string(record).map_err(|err| err.to_string())?);
+                    str.push('\n');
+                }
+                Ok(str)
+            }
+        }
+    }
+}
+
+// dates and times TOML has a type for are strings in JSON, like the schema expects
+fn from_toml_value(toml_value: &toml::Value) -> Value {
+    match toml_value {
+        toml::Value::String(str) => Value::String(str.clone()),
+        toml::Value::Integer(int) => Value::from(*int),
+        toml::Value::Float(float) => Value::from(*float),
+        toml::Value::Boolean(bool_value) => Value::Bool(*bool_value),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => Value::Array(items.iter().map(from_toml_value).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, inner)| (key.clone(), from_toml_value(inner)))
+                .collect(),
+        ),
+    }
+}
+
+// the JSON pointer of the first null that's an item of an array
+fn find_null_in_array(value: &Value, pointer: &str) -> Option<String> {
+    match value {
+        Value::Array(items) => items.iter().enumerate().find_map(|(index, item)| {
+            let item_pointer = format!("{pointer}/{index}");
+            if item.is_null() {
+                Some(item_pointer)
+            } else {
+                find_null_in_array(item, &item_pointer)
+            }
+        }),
+        Value::Object(object) => object
+            .iter()
+            .find_map(|(key, inner)| find_null_in_array(inner, &format!("{pointer}/{key}"))),
+        _ => None,
+    }
+}
+
+fn without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.iter().map(without_nulls).collect()),
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .filter(|(_, inner)| !inner.is_null())
+                .map(|(key, inner)| (key.clone(), without_nulls(inner)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+// one file of data split across a directory or glob, with the records read from it
+pub struct DataFile {
+    pub path: PathBuf,
+    pub len: usize,
+    // a single record rather than an array, which is how it's written back
+    pub is_record: bool,
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+fn has_data_extension(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        DATA_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str())
+    })
+}
+
+// dataJson can be a directory or glob of files, read together as one array
+pub fn is_split(data_path: &Path) -> bool {
+    data_path.is_dir() || is_glob(data_path)
+}
+
+// in file name order, which is the order of the records read from them
+pub fn get_data_paths(data_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut paths: Vec<PathBuf> = if data_path.is_dir() {
+        std::fs::read_dir(data_path)
+            .map_err(|err| format!("Should read {}: {err}", style_path(data_path, "data")))?
+            .filter_map(|entry_res| entry_res.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                // like editor swap files, which are hidden
+                let is_hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                path.is_file() && !is_hidden && has_data_extension(path)
+            })
+            .collect()
+    } else if is_glob(data_path) {
+        glob::glob(&data_path.to_string_lossy())
+            .map_err(|err| format!("{} should be a valid glob: {err}", data_path.display()))?
+            .filter_map(|path_res| path_res.ok())
+            .filter(|path| path.is_file())
+            .collect()
+    } else {
+        return Ok(vec![data_path.to_path_buf()]);
+    };
+    paths.sort();
+    Ok(paths)
+}
+
+// whether a path is, or was, one of the files of the data, like one in a snapshot
+pub fn is_data_path(data_path: &Path, path: &Path) -> bool {
+    // paths like ././data and ./data are the same, but only match as globs once normalized
+    let normalize = |path: &Path| path.components().collect::<PathBuf>();
+    if is_glob(data_path) {
+        glob::Pattern::new(&normalize(data_path).to_string_lossy())
+            .is_ok_and(|pattern| pattern.matches_path(&normalize(path)))
+    } else if data_path.is_dir() {
+        path.parent() == Some(data_path) && has_data_extension(path)
+    } else {
+        path == data_path
+    }
+}
+
+// the records of one file of split data, which holds an array of them or just one
+pub fn get_records(value: Value) -> Option<(Vec<Value>, bool)> {
+    match value {
+        Value::Array(records) => Some((records, false)),
+        Value::Object(_) => Some((vec![value], true)),
+        _ => None,
+    }
+}
+
+fn parse_file(path: &Path, str: &str) -> Result<Value, String> {
+    let format = DataFormat::from_path(path);
+    format.parse(str).map_err(|err| {
+        format!(
+            "Should parse {} as {}: {err}",
+            style_path(path, "data"),
+            format.name()
+        )
+    })
+}
+
+// the records of every file as one array, with which file each came from
+pub fn combine_data_files(
+    contents: &[(PathBuf, String)],
+) -> Result<(Value, Vec<DataFile>), String> {
+    let mut records: Vec<Value> = Vec::new();
+    let mut files: Vec<DataFile> = Vec::new();
+    for (path, str) in contents {
+        let (file_records, is_record) = get_records(parse_file(path, str)?).ok_or(format!(
+            "{} should hold a record or an array of records",
+            style_path(path, "data")
+        ))?;
+        files.push(DataFile {
+            path: path.clone(),
+            len: file_records.len(),
+            is_record,
+        });
+        records.extend(file_records);
+    }
+    Ok((Value::Array(records), files))
+}
+
+pub fn read_data_file(path: &Path) -> Value {
+    try_read_data_file(path).unwrap_or_else(|err| panic!("{err}"))
+}
+
+pub fn try_read_data_file(path: &Path) -> Result<Value, String> {
+    try_read_data_files(path).map(|(data_json, _)| data_json)
+}
+
+// files are only listed for split data, since a single file needs no locating
+pub fn try_read_data_files(data_path: &Path) -> Result<(Value, Vec<DataFile>), String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .map_err(|err| format!("Should read {}: {err}", style_path(path, "data")))
+    };
+    if !is_split(data_path) {
+        return Ok((parse_file(data_path, &read(data_path)?)?, Vec::new()));
+    }
+    let mut contents: Vec<(PathBuf, String)> = Vec::new();
+    for path in get_data_paths(data_path)? {
+        let str = read(&path)?;
+        contents.push((path, str));
+    }
+    combine_data_files(&contents)
+}
+
+// in the format the file already has, so edits by heda don't change how it's written
+fn to_data_string<T: serde::Serialize>(path: &Path, data: &T) -> Result<String, String> {
+    let format = DataFormat::from_path(path);
+    let data_json = serde_json::to_value(data).expect("turned data to JSON");
+    format.to_string(&data_json).map_err(|err| {
+        format!(
+            "Could not write {} as {}: {err}",
+            style_path(path, "data"),
+            format.name()
+        )
+    })
+}
+
+pub fn write_data_file<T: serde::Serialize>(path: &Path, data: &T) {
+    let data_str = to_data_string(path, data).unwrap_or_else(|err| panic!("{err}"));
+    std::fs::write(path, data_str)
+        .expect(format!("Should write {}", style_path(path, "data")).as_str());
+}
+
+// the files writing data changes, worked out before any are written so a failure leaves none half done
+pub struct DataWrite {
+    // None for files whose records were all removed
+    files: Vec<(PathBuf, Option<String>)>,
+}
+
+impl DataWrite {
+    // YAML is written from its data, which doesn't keep comments
+    pub fn yaml_paths(&self) -> Vec<&Path> {
+        self.files
+            .iter()
+            .filter(|(path, contents_opt)| {
+                contents_opt.is_some() && DataFormat::from_path(path) == DataFormat::Yaml
+            })
+            .map(|(path, _)| path.as_path())
+            .collect()
+    }
+
+    pub fn write(self) {
+        for (path, contents_opt) in self.files {
+            match contents_opt {
+                Some(contents) => std::fs::write(&path, contents)
+                    .expect(format!("Should write {}", style_path(&path, "data")).as_str()),
+                None => std::fs::remove_file(&path)
+                    .expect(format!("Should remove {}", style_path(&path, "data")).as_str()),
+            }
+        }
+    }
+}
+
+// records go back to the file they came from, so only files with changed records are written
+// when records are added or removed, they're matched to their file by primary key
+pub fn plan_data_files(
+    data_path: &Path,
+    data: &[Datum],
+    primary_key: Option<&str>,
+) -> Result<DataWrite, String> {
+    if !is_split(data_path) {
+        let contents = to_data_string(data_path, &data)?;
+        return Ok(DataWrite {
+            files: vec![(data_path.to_path_buf(), Some(contents))],
+        });
+    }
+    let (old_data_json, files) = try_read_data_files(data_path)?;
+    let old_records = old_data_json.as_array().cloned().unwrap_or_default();
+    let records: Vec<Value> = data.iter().cloned().map(Value::Object).collect();
+
+    let mut file_records: Vec<Vec<Value>> = files.iter().map(|_| Vec::new()).collect();
+    let mut added_records: Vec<Value> = Vec::new();
+    let mut file_indexes: Vec<usize> = Vec::new();
+    for (file_index, file) in files.iter().enumerate() {
+        file_indexes.extend(std::iter::repeat(file_index).take(file.len));
+    }
+    if records.len() == old_records.len() {
+        for (record, file_index) in records.into_iter().zip(&file_indexes) {
+            file_records[*file_index].push(record);
+        }
+    } else {
+        let primary_key = primary_key.ok_or(format!(
+            "{} needs a {} to add or remove records, which matches them to their files",
+            style_path(data_path, "data"),
+            console::style("primaryKey").italic()
+        ))?;
+        // unchanged records stay in their file, even when their key is duplicated in another
+        let mut files_by_record: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut file_by_key: HashMap<String, usize> = HashMap::new();
+        for (record, file_index) in old_records.iter().zip(&file_indexes).rev() {
+            files_by_record
+                .entry(record.to_string())
+                .or_default()
+                .push(*file_index);
+            if let Some(key) = record.get(primary_key) {
+                file_by_key.insert(key.to_string(), *file_index);
+            }
+        }
+        for record in records {
+            let file_index_opt = files_by_record
+                .get_mut(&record.to_string())
+                .and_then(|file_indexes| file_indexes.pop())
+                .or_else(|| {
+                    let key = record.get(primary_key)?;
+                    file_by_key.get(&key.to_string()).copied()
+                });
+            match file_index_opt {
+                Some(file_index) => file_records[file_index].push(record),
+                None => added_records.push(record),
+            }
+        }
+    }
+
+    // a directory of single records gets a file for each added one, otherwise they join the last file
+    let adds_files = data_path.is_dir() && files.last().map_or(true, |file| file.is_record);
+    if !adds_files && !added_records.is_empty() {
+        match file_records.last_mut() {
+            Some(last_records) => last_records.append(&mut added_records),
+            None => {
+                return Err(format!(
+                    "{} has no files to add records to",
+                    style_path(data_path, "data")
+                ))
+            }
+        }
+    }
+
+    let mut planned_files: Vec<(PathBuf, Option<String>)> = Vec::new();
+    let mut old_records_iter = old_records.into_iter();
+    for (file, records) in files.iter().zip(file_records) {
+        let old_file_records: Vec<Value> = old_records_iter.by_ref().take(file.len).collect();
+        if records == old_file_records {
+            continue;
+        }
+        let contents_opt = match (file.is_record, records.as_slice()) {
+            (true, []) => None,
+            (true, [record]) => Some(to_data_string(&file.path, record)?),
+            _ => Some(to_data_string(&file.path, &records)?),
+        };
+        planned_files.push((file.path.clone(), contents_opt));
+    }
+
+    let extension = files
+        .last()
+        .and_then(|file| file.path.extension())
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or(DEFAULT_EXTENSION.to_string());
+    for record in added_records {
+        let planned_paths: Vec<&Path> = planned_files
+            .iter()
+            .map(|(path, _)| path.as_path())
+            .collect();
+        let path = get_record_path(data_path, &record, primary_key, &extension, &planned_paths);
+        let contents = to_data_string(&path, &record)?;
+        planned_files.push((path, Some(contents)));
+    }
+    Ok(DataWrite {
+        files: planned_files,
+    })
+}
+
+// named by primary key where there is one, with a counter for names already taken
+fn get_record_path(
+    data_path: &Path,
+    record: &Value,
+    primary_key: Option<&str>,
+    extension: &str,
+    // of other records added alongside it, which don't exist yet
+    planned_paths: &[&Path],
+) -> PathBuf {
+    let key_str = match primary_key.and_then(|primary_key| record.get(primary_key)) {
+        Some(Value::String(key)) => key.clone(),
+        Some(key) => key.to_string(),
+        None => "record".to_string(),
+    };
+    let stem: String = key_str
+        .chars()
+        .map(|char| {
+            if char.is_alphanumeric() || char == '-' || char == '_' {
+                char
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut path = data_path.join(format!("{stem}.{extension}"));
+    let mut counter = 1;
+    while path.exists() || planned_paths.contains(&path.as_path()) {
+        counter += 1;
+        path = data_path.join(format!("{stem}-{counter}.{extension}"));
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    // a fresh directory for each test, since they run in parallel
+    fn make_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("heda-data-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn to_data(records: Value) -> Vec<Datum> {
+        serde_json::from_value(records).unwrap()
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn write(data_path: &Path, records: Value, primary_key: Option<&str>) {
+        plan_data_files(data_path, &to_data(records), primary_key)
+            .unwrap()
+            .write();
+    }
+
+    #[test]
+    fn equal_length_data_goes_back_to_the_same_files() {
+        let dir = make_dir("equal");
+        // compact, so a rewrite would show as pretty printed
+        std::fs::write(dir.join("a.json"), r#"[{"id":1},{"id":2}]"#).unwrap();
+        std::fs::write(dir.join("b.yaml"), "- id: 3\n").unwrap();
+        write(&dir, json!([{ "id": 1 }, { "id": 2 }, { "id": 4 }]), None);
+        assert_eq!(read(&dir.join("a.json")), r#"[{"id":1},{"id":2}]"#);
+        assert_eq!(read(&dir.join("b.yaml")), "- id: 4\n");
+    }
+
+    #[test]
+    fn records_are_added_and_removed_by_primary_key() {
+        let dir = make_dir("key");
+        std::fs::write(dir.join("a.json"), r#"[{"id":1},{"id":2}]"#).unwrap();
+        std::fs::write(dir.join("b.json"), r#"[{"id":3}]"#).unwrap();
+        // 2 is removed, 3 is edited, and 4 and 5 are added to the last file
+        let records = json!([
+            { "id": 1 },
+            { "id": 3, "edited": true },
+            { "id": 4 },
+            { "id": 5 },
+        ]);
+        write(&dir, records.clone(), Some("id"));
+        let (data_json, files) = try_read_data_files(&dir).unwrap();
+        assert_eq!(data_json, records);
+        assert_eq!(files[0].len, 1);
+        assert_eq!(files[1].len, 3);
+    }
+
+    #[test]
+    fn adding_or_removing_records_without_primary_key_fails_before_writing() {
+        let dir = make_dir("no-key");
+        std::fs::write(dir.join("a.json"), r#"[{"id":1},{"id":2}]"#).unwrap();
+        let plan_res = plan_data_files(&dir, &to_data(json!([{ "id": 1 }])), None);
+        assert!(plan_res.is_err());
+        assert_eq!(read(&dir.join("a.json")), r#"[{"id":1},{"id":2}]"#);
+    }
+
+    #[test]
+    fn single_record_files_are_added_and_removed() {
+        let dir = make_dir("single");
+        std::fs::write(dir.join("a.json"), r#"{"id":"a"}"#).unwrap();
+        std::fs::write(dir.join("b.json"), r#"{"id":"b"}"#).unwrap();
+        write(
+            &dir,
+            json!([{ "id": "b" }, { "id": "c" }, { "id": "c/2" }]),
+            Some("id"),
+        );
+        assert!(!dir.join("a.json").exists());
+        assert_eq!(read(&dir.join("b.json")), r#"{"id":"b"}"#);
+        assert_eq!(
+            read(&dir.join("c.json")),
+            serde_json::to_string_pretty(&json!({ "id": "c" })).unwrap()
+        );
+        assert!(dir.join("c_2.json").is_file());
+    }
+
+    #[test]
+    fn a_glob_adds_to_its_last_file_while_a_directory_adds_files() {
+        let dir = make_dir("glob");
+        std::fs::write(dir.join("a.json"), r#"{"id":"a"}"#).unwrap();
+        std::fs::write(dir.join("b.json"), r#"{"id":"b"}"#).unwrap();
+        std::fs::write(dir.join("other.yaml"), "- id: other\n").unwrap();
+        let glob_path = dir.join("*.json");
+        write(
+            &glob_path,
+            json!([{ "id": "a" }, { "id": "b" }, { "id": "c" }]),
+            Some("id"),
+        );
+        assert!(!dir.join("c.json").exists());
+        assert_eq!(
+            try_read_data_file(&dir.join("b.json")).unwrap(),
+            json!([{ "id": "b" }, { "id": "c" }])
+        );
+        assert_eq!(read(&dir.join("other.yaml")), "- id: other\n");
+
+        let dir = make_dir("directory");
+        std::fs::write(dir.join("a.json"), r#"{"id":"a"}"#).unwrap();
+        write(&dir, json!([{ "id": "a" }, { "id": "b" }]), Some("id"));
+        assert_eq!(
+            try_read_data_file(&dir.join("b.json")).unwrap(),
+            json!({ "id": "b" })
+        );
+    }
+
+    #[test]
+    fn toml_drops_null_fields_but_fails_on_nulls_in_arrays() {
+        let dir = make_dir("toml");
+        let data_path = dir.join("data.toml");
+        write(&data_path, json!([{ "id": 1, "note": null }]), None);
+        assert_eq!(
+            try_read_data_file(&data_path).unwrap(),
+            json!([{ "id": 1 }])
+        );
+        let err = plan_data_files(&data_path, &to_data(json!([{ "tags": ["a", null] }])), None)
+            .err()
+            .unwrap();
+        assert!(err.contains("/0/tags/1"));
+    }
+}
@@ -1 +1,54 @@
+use serde_json::Value;
+
 pub type Datum = serde_json::Map<String, serde_json::Value>;
+
+// every dataset of a workspace by name, for scripts working across them
+pub type Collections = std::collections::BTreeMap<String, Vec<Datum>>;
+
+// fields can be nested with dots, like address.city
+pub fn get_field<'a>(datum: &'a Datum, path: &str) -> Option<&'a Value> {
+    let mut segments = path.split('.');
+    // unwrap is safe, since split always has a first segment
+    let mut value = datum.get(segments.next().unwrap())?;
+    for segment in segments {
+        value = value.as_object()?.get(segment)?;
+    }
+    Some(value)
+}
+
+pub fn take_field(datum: &mut Datum, path: &str) -> Option<Value> {
+    match path.rsplit_once('.') {
+        None => datum.remove(path),
+        Some((parent_path, key)) => {
+            let mut segments = parent_path.split('.');
+            // unwrap is safe, since split always has a first segment
+            let mut parent = datum.get_mut(segments.next().unwrap())?;
+            for segment in segments {
+                parent = parent.as_object_mut()?.get_mut(segment)?;
+            }
+            parent.as_object_mut()?.remove(key)
+        }
+    }
+}
+
+// creates missing parent objects, but won't replace a parent that isn't an object
+pub fn set_field(datum: &mut Datum, path: &str, value: Value) -> Result<(), String> {
+    let segments: Vec<&str> = path.split('.').collect();
+    // unwrap is safe, since split always has a last segment
+    let (key, parent_segments) = segments.split_last().unwrap();
+    let mut parent = datum;
+    for (depth, segment) in parent_segments.iter().enumerate() {
+        let child = parent
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+        if child.is_null() {
+            *child = Value::Object(serde_json::Map::new());
+        }
+        parent = child.as_object_mut().ok_or(format!(
+            "{} should be an object to set {path}",
+            segments[..=depth].join(".")
+        ))?;
+    }
+    parent.insert(key.to_string(), value);
+    Ok(())
+}
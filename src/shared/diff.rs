@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use super::datum::Datum;
+
+#[derive(serde::Serialize)]
+#[serde(tag = "change", rename_all = "camelCase")]
+pub enum RecordChange {
+    Added {
+        key: String,
+        record: Datum,
+    },
+    Removed {
+        key: String,
+        record: Datum,
+    },
+    Modified {
+        key: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+#[derive(serde::Serialize)]
+pub struct FieldChange {
+    // dotted path into nested objects, like address.city
+    pub field: String,
+    // None when the field is missing, unlike a null value
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl RecordChange {
+    pub fn key(&self) -> &str {
+        match self {
+            RecordChange::Added { key, .. }
+            | RecordChange::Removed { key, .. }
+            | RecordChange::Modified { key, .. } => key,
+        }
+    }
+}
+
+// records repeating a key are matched by how many times it was seen
+// values are in their JSON form, so "1" and 1 aren't taken for the same record
+pub fn get_keys(data: &[Datum], primary_key: Option<&str>) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    data.iter()
+        .enumerate()
+        .map(|(index, datum)| {
+            let key = match primary_key.and_then(|field| datum.get(field)) {
+                Some(value) if !value.is_null() => {
+                    format!("{} {value}", primary_key.unwrap_or_default())
+                }
+                // without a key, records are matched by where they are
+                _ => format!("record {index}"),
+            };
+            let count = seen.entry(key.clone()).or_default();
+            *count += 1;
+            if *count == 1 {
+                key
+            } else {
+                format!("{key} ({count})")
+            }
+        })
+        .collect()
+}
+
+// removed records first, then added and modified ones in their new order
+pub fn diff_data(
+    old_data: &[Datum],
+    new_data: &[Datum],
+    primary_key: Option<&str>,
+) -> Vec<RecordChange> {
+    let old_keys = get_keys(old_data, primary_key);
+    let new_keys = get_keys(new_data, primary_key);
+    let old_by_key: HashMap<&String, &Datum> = old_keys.iter().zip(old_data).collect();
+    let new_by_key: HashMap<&String, &Datum> = new_keys.iter().zip(new_data).collect();
+
+    let mut changes: Vec<RecordChange> = Vec::new();
+    for (key, old_datum) in old_keys.iter().zip(old_data) {
+        if !new_by_key.contains_key(key) {
+            changes.push(RecordChange::Removed {
+                key: key.clone(),
+                record: old_datum.clone(),
+            });
+        }
+    }
+    for (key, new_datum) in new_keys.iter().zip(new_data) {
+        match old_by_key.get(key) {
+            None => changes.push(RecordChange::Added {
+                key: key.clone(),
+                record: new_datum.clone(),
+            }),
+            Some(old_datum) => {
+                let mut fields: Vec<FieldChange> = Vec::new();
+                diff_objects(old_datum, new_datum, "", &mut fields);
+                if !fields.is_empty() {
+                    changes.push(RecordChange::Modified {
+                        key: key.clone(),
+                        fields,
+                    });
+                }
+            }
+        }
+    }
+    changes
+}
+
+// nested objects are compared field by field, and anything else as a whole
+fn diff_objects(
+    old_object: &Datum,
+    new_object: &Datum,
+    prefix: &str,
+    fields: &mut Vec<FieldChange>,
+) {
+    let mut keys: Vec<&String> = old_object.keys().collect();
+    keys.extend(
+        new_object
+            .keys()
+            .filter(|key| !old_object.contains_key(*key)),
+    );
+    for key in keys {
+        let field = format!("{prefix}{key}");
+        match (old_object.get(key), new_object.get(key)) {
+            (Some(Value::Object(old_inner)), Some(Value::Object(new_inner))) => {
+                diff_objects(old_inner, new_inner, &format!("{field}."), fields)
+            }
+            (old_value, new_value) if old_value != new_value => fields.push(FieldChange {
+                field,
+                old: old_value.cloned(),
+                new: new_value.cloned(),
+            }),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn to_data(value: Value) -> Vec<Datum> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn keys_tell_strings_from_numbers() {
+        let old_data = to_data(json!([{ "id": "1", "name": "a" }]));
+        let new_data = to_data(json!([{ "id": 1, "name": "a" }]));
+        assert_eq!(get_keys(&old_data, Some("id")), vec![r#"id "1""#]);
+        assert_eq!(get_keys(&new_data, Some("id")), vec!["id 1"]);
+        let changes = diff_data(&old_data, &new_data, Some("id"));
+        assert_eq!(changes.len(), 2);
+    }
+
+    // fields of the only change, which should be a modification
+    fn get_modified_fields(changes: &[RecordChange]) -> Vec<(&str, Option<Value>, Option<Value>)> {
+        match changes {
+            [RecordChange::Modified { fields, .. }] => fields
+                .iter()
+                .map(|change| {
+                    (
+                        change.field.as_str(),
+                        change.old.clone(),
+                        change.new.clone(),
+                    )
+                })
+                .collect(),
+            _ => panic!("expected one modified record"),
+        }
+    }
+
+    #[test]
+    fn nested_fields_are_diffed_by_path() {
+        let old_data = to_data(json!([{
+            "id": 1,
+            "address": { "city": "Oslo", "street": { "name": "a", "number": 1 } },
+            "note": null,
+        }]));
+        let new_data = to_data(json!([{
+            "id": 1,
+            "address": { "city": "Bergen", "street": { "name": "a", "number": 2 } },
+            "tags": null,
+        }]));
+        let changes = diff_data(&old_data, &new_data, Some("id"));
+        assert_eq!(
+            get_modified_fields(&changes),
+            vec![
+                ("address.city", Some(json!("Oslo")), Some(json!("Bergen"))),
+                ("address.street.number", Some(json!(1)), Some(json!(2))),
+                // a null value isn't the same as a missing field
+                ("note", Some(Value::Null), None),
+                ("tags", None, Some(Value::Null)),
+            ]
+        );
+    }
+
+    #[test]
+    fn objects_replacing_other_values_are_diffed_as_a_whole() {
+        let old_data = to_data(json!([{ "id": 1, "address": "Oslo" }]));
+        let new_data = to_data(json!([{ "id": 1, "address": { "city": "Oslo" } }]));
+        let changes = diff_data(&old_data, &new_data, Some("id"));
+        assert_eq!(
+            get_modified_fields(&changes),
+            vec![(
+                "address",
+                Some(json!("Oslo")),
+                Some(json!({ "city": "Oslo" }))
+            )]
+        );
+    }
+
+    #[test]
+    fn repeated_keys_are_matched_by_occurrence() {
+        let old_data = to_data(json!([
+            { "id": 1, "name": "a" },
+            { "id": 2, "name": "b" },
+            { "id": 1, "name": "c" },
+        ]));
+        let new_data = to_data(json!([
+            { "id": 1, "name": "a" },
+            { "id": 1, "name": "d" },
+            { "id": 1, "name": "e" },
+        ]));
+        assert_eq!(
+            get_keys(&new_data, Some("id")),
+            vec!["id 1", "id 1 (2)", "id 1 (3)"]
+        );
+        let changes = diff_data(&old_data, &new_data, Some("id"));
+        let summary: Vec<(&str, &str)> = changes
+            .iter()
+            .map(|change| match change {
+                RecordChange::Added { key, .. } => ("added", key.as_str()),
+                RecordChange::Removed { key, .. } => ("removed", key.as_str()),
+                RecordChange::Modified { key, .. } => ("modified", key.as_str()),
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("removed", "id 2"),
+                ("modified", "id 1 (2)"),
+                ("added", "id 1 (3)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_without_a_key_are_matched_by_position() {
+        let old_data = to_data(json!([{ "name": "a" }, { "id": null, "name": "b" }]));
+        let new_data = to_data(json!([{ "name": "a" }, { "id": null, "name": "c" }]));
+        assert_eq!(get_keys(&old_data, None), vec!["record 0", "record 1"]);
+        // a null key counts as no key
+        assert_eq!(
+            get_keys(&old_data, Some("id")),
+            vec!["record 0", "record 1"]
+        );
+        let changes = diff_data(&old_data, &new_data, Some("id"));
+        assert_eq!(changes[0].key(), "record 1");
+        assert_eq!(
+            get_modified_fields(&changes),
+            vec![("name", Some(json!("b")), Some(json!("c")))]
+        );
+    }
+}
@@ -1,24 +1,53 @@
 use std::collections::HashMap;
 
 // groups errors with the same error type
-pub struct RunningErrors(HashMap<String, Vec<String>>);
+pub struct RunningErrors {
+    errs: HashMap<String, Vec<String>>,
+    warns: HashMap<String, Vec<String>>,
+}
 
 impl RunningErrors {
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self {
+            errs: HashMap::new(),
+            warns: HashMap::new(),
+        }
     }
 
     pub fn add_err(&mut self, err_type: &String, err_details: String) {
-        if !self.0.contains_key(err_type) {
-            self.0.insert(err_type.clone(), Vec::new());
+        Self::add_to(&mut self.errs, err_type, err_details);
+    }
+
+    pub fn add_warn(&mut self, warn_type: &String, warn_details: String) {
+        Self::add_to(&mut self.warns, warn_type, warn_details);
+    }
+
+    pub fn extend(&mut self, other: RunningErrors) {
+        for (err_type, details) in other.errs {
+            for detail in details {
+                self.add_err(&err_type, detail);
+            }
         }
-        // unwrap is safe, since we insert if key does not already exist
-        self.0.get_mut(err_type).unwrap().push(err_details);
+        for (warn_type, details) in other.warns {
+            for detail in details {
+                self.add_warn(&warn_type, detail);
+            }
+        }
+    }
+
+    pub fn has_errs(&self) -> bool {
+        !self.errs.is_empty()
     }
 
     pub fn print_errs(&mut self) {
-        if !self.0.is_empty() {
-            for (os_err, human_errs) in self.0.iter() {
+        for (os_warn, human_warns) in self.warns.iter() {
+            eprintln!("{} {}", console::style("Warning:").yellow(), os_warn);
+            for warn in human_warns {
+                eprintln!("  {}", warn);
+            }
+        }
+        if !self.errs.is_empty() {
+            for (os_err, human_errs) in self.errs.iter() {
                 eprintln!("{} {}", console::style("Fatal:").red(), os_err);
                 for err in human_errs {
                     eprintln!("  {}", err);
@@ -26,4 +55,12 @@ impl RunningErrors {
             }
         }
     }
+
+    fn add_to(map: &mut HashMap<String, Vec<String>>, key: &String, details: String) {
+        if !map.contains_key(key) {
+            map.insert(key.clone(), Vec::new());
+        }
+        // unwrap is safe, since we insert if key does not already exist
+        map.get_mut(key).unwrap().push(details);
+    }
 }
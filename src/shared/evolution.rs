@@ -0,0 +1,469 @@
+use std::collections::HashSet;
+
+use super::type_rs::{FieldDef, TypeDef, TypeExpr, TypeFile};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    // existing data still fits the new schema
+    Compatible,
+    // existing data may not fit the new schema, so needs a migration
+    Breaking,
+}
+
+pub struct SchemaChange {
+    pub kind: ChangeKind,
+    // dotted path from the root struct, like Datum.address.zip
+    pub path: String,
+    pub message: String,
+}
+
+impl SchemaChange {
+    pub fn fmt(&self) -> String {
+        let kind_str = match self.kind {
+            ChangeKind::Compatible => console::style("compatible").green(),
+            ChangeKind::Breaking => console::style("breaking").red(),
+        };
+        format!("{kind_str} {}: {}", self.path, self.message)
+    }
+}
+
+// every difference between schemas made from type.rs, as it changed from old to new
+pub fn compare_schemas(
+    old_schema: &serde_json::Value,
+    new_schema: &serde_json::Value,
+) -> Result<Vec<SchemaChange>, String> {
+    let old_file = TypeFile::from_schema(old_schema)?;
+    let new_file = TypeFile::from_schema(new_schema)?;
+    let mut comparer = Comparer {
+        old_file: &old_file,
+        new_file: &new_file,
+        visited: HashSet::new(),
+        changes: Vec::new(),
+    };
+    comparer.compare_named(&old_file.root, &new_file.root, &new_file.root);
+    Ok(comparer.changes)
+}
+
+pub fn has_breaking(changes: &[SchemaChange]) -> bool {
+    changes
+        .iter()
+        .any(|change| change.kind == ChangeKind::Breaking)
+}
+
+struct Comparer<'a> {
+    old_file: &'a TypeFile,
+    new_file: &'a TypeFile,
+    // pairs of old and new type names, since types can refer to themselves
+    visited: HashSet<(String, String)>,
+    changes: Vec<SchemaChange>,
+}
+
+impl Comparer<'_> {
+    fn push(&mut self, kind: ChangeKind, path: &str, message: String) {
+        self.changes.push(SchemaChange {
+            kind,
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    // types are compared by what they contain, so renaming one isn't a change
+    fn compare_named(&mut self, old_name: &str, new_name: &str, path: &str) {
+        if !self
+            .visited
+            .insert((old_name.to_string(), new_name.to_string()))
+        {
+            return;
+        }
+        let old_def = self.old_file.defs.iter().find(|def| def.name() == old_name);
+        let new_def = self.new_file.defs.iter().find(|def| def.name() == new_name);
+        match (old_def, new_def) {
+            (Some(TypeDef::Struct(old_struct)), Some(TypeDef::Struct(new_struct))) => {
+                match (
+                    old_struct.deny_unknown_fields,
+                    new_struct.deny_unknown_fields,
+                ) {
+                    (false, true) => self.push(
+                        ChangeKind::Breaking,
+                        path,
+                        "now denies unknown fields".to_string(),
+                    ),
+                    (true, false) => self.push(
+                        ChangeKind::Compatible,
+                        path,
+                        "now allows unknown fields".to_string(),
+                    ),
+                    _ => {}
+                }
+                for old_field in &old_struct.fields {
+                    let field_path = format!("{path}.{}", old_field.name);
+                    match new_struct
+                        .fields
+                        .iter()
+                        .find(|new_field| new_field.name == old_field.name)
+                    {
+                        Some(new_field) => self.compare_fields(old_field, new_field, &field_path),
+                        None => self.push(
+                            ChangeKind::Breaking,
+                            &field_path,
+                            "removed field".to_string(),
+                        ),
+                    }
+                }
+                for new_field in &new_struct.fields {
+                    if old_struct
+                        .fields
+                        .iter()
+                        .any(|old_field| old_field.name == new_field.name)
+                    {
+                        continue;
+                    }
+                    let field_path = format!("{path}.{}", new_field.name);
+                    if is_optional(new_field) {
+                        self.push(
+                            ChangeKind::Compatible,
+                            &field_path,
+                            "new optional field".to_string(),
+                        );
+                    } else {
+                        self.push(
+                            ChangeKind::Breaking,
+                            &field_path,
+                            "new required field".to_string(),
+                        );
+                    }
+                }
+            }
+            (Some(TypeDef::Enum(old_enum)), Some(TypeDef::Enum(new_enum))) => {
+                for variant in &old_enum.variants {
+                    if !new_enum.variants.contains(variant) {
+                        self.push(
+                            ChangeKind::Breaking,
+                            path,
+                            format!("removed enum value {variant:?}"),
+                        );
+                    }
+                }
+                for variant in &new_enum.variants {
+                    if !old_enum.variants.contains(variant) {
+                        self.push(
+                            ChangeKind::Compatible,
+                            path,
+                            format!("new enum value {variant:?}"),
+                        );
+                    }
+                }
+            }
+            _ => self.push(
+                ChangeKind::Breaking,
+                path,
+                format!("changed type from {old_name} to {new_name}"),
+            ),
+        }
+    }
+
+    fn compare_fields(&mut self, old_field: &FieldDef, new_field: &FieldDef, path: &str) {
+        let (old_optional, new_optional) = (is_optional(old_field), is_optional(new_field));
+        match (old_optional, new_optional) {
+            (false, true) => self.push(ChangeKind::Compatible, path, "now optional".to_string()),
+            (true, false) => self.push(ChangeKind::Breaking, path, "now required".to_string()),
+            _ => {}
+        }
+        // Option changes both at once, so nullability is only mentioned on its own
+        let (old_ty, old_nullable) = unwrap_option(&old_field.ty);
+        let (new_ty, new_nullable) = unwrap_option(&new_field.ty);
+        if old_optional == new_optional {
+            match (old_nullable, new_nullable) {
+                (false, true) => {
+                    self.push(ChangeKind::Compatible, path, "now nullable".to_string())
+                }
+                (true, false) if !matches!(new_ty, TypeExpr::Value) => {
+                    self.push(ChangeKind::Breaking, path, "no longer nullable".to_string())
+                }
+                _ => {}
+            }
+        }
+        self.compare_types(old_ty, new_ty, path);
+    }
+
+    fn compare_types(&mut self, old_ty: &TypeExpr, new_ty: &TypeExpr, path: &str) {
+        match (old_ty, new_ty) {
+            (TypeExpr::Option(old_inner), TypeExpr::Option(new_inner)) => {
+                self.compare_types(old_inner, new_inner, path)
+            }
+            // nested types, like items of an array, can change nullability too
+            (TypeExpr::Option(old_inner), _) => {
+                if !matches!(new_ty, TypeExpr::Value) {
+                    self.push(ChangeKind::Breaking, path, "no longer nullable".to_string());
+                }
+                self.compare_types(old_inner, new_ty, path)
+            }
+            (_, TypeExpr::Option(new_inner)) => {
+                self.push(ChangeKind::Compatible, path, "now nullable".to_string());
+                self.compare_types(old_ty, new_inner, path)
+            }
+            (TypeExpr::Named(old_name), TypeExpr::Named(new_name)) => {
+                self.compare_named(old_name, new_name, path)
+            }
+            (TypeExpr::Vec(old_inner), TypeExpr::Vec(new_inner))
+            | (TypeExpr::Set(old_inner), TypeExpr::Set(new_inner))
+            | (TypeExpr::Map(old_inner), TypeExpr::Map(new_inner)) => {
+                self.compare_types(old_inner, new_inner, &format!("{path}[]"))
+            }
+            (TypeExpr::Set(old_inner), TypeExpr::Vec(new_inner)) => {
+                self.push(
+                    ChangeKind::Compatible,
+                    path,
+                    "items no longer need to be unique".to_string(),
+                );
+                self.compare_types(old_inner, new_inner, &format!("{path}[]"))
+            }
+            (TypeExpr::Vec(old_inner), TypeExpr::Set(new_inner)) => {
+                self.push(
+                    ChangeKind::Breaking,
+                    path,
+                    "items now need to be unique".to_string(),
+                );
+                self.compare_types(old_inner, new_inner, &format!("{path}[]"))
+            }
+            _ if old_ty == new_ty => {}
+            _ => {
+                let kind = if is_widened(old_ty, new_ty) {
+                    ChangeKind::Compatible
+                } else {
+                    ChangeKind::Breaking
+                };
+                let message = format!(
+                    "changed type from {} to {}",
+                    fmt_type(old_ty),
+                    fmt_type(new_ty)
+                );
+                self.push(kind, path, message);
+            }
+        }
+    }
+}
+
+fn unwrap_option(ty: &TypeExpr) -> (&TypeExpr, bool) {
+    match ty {
+        TypeExpr::Option(inner) => (inner, true),
+        _ => (ty, false),
+    }
+}
+
+fn is_optional(field: &FieldDef) -> bool {
+    field.has_default || matches!(field.ty, TypeExpr::Option(_))
+}
+
+// whether every value of the old type is a value of the new one
+fn is_widened(old_ty: &TypeExpr, new_ty: &TypeExpr) -> bool {
+    match (old_ty, new_ty) {
+        (_, TypeExpr::Value) => true,
+        (TypeExpr::Date | TypeExpr::DateTime, TypeExpr::Primitive(new_name)) => {
+            new_name == "String"
+        }
+        (TypeExpr::Primitive(old_name), TypeExpr::Primitive(new_name)) => {
+            match (int_range(old_name), int_range(new_name)) {
+                (Some((old_min, old_max)), Some((new_min, new_max))) => {
+                    new_min <= old_min && old_max <= new_max
+                }
+                // every integer heda stores as JSON fits a float
+                (Some(_), None) => new_name == "f64" || new_name == "f32",
+                (None, _) => {
+                    (old_name == "f32" && new_name == "f64")
+                        || (old_name == "char" && new_name == "String")
+                }
+            }
+        }
+        _ => false,
+    }
+}
+
+fn int_range(name: &str) -> Option<(i128, i128)> {
+    Some(match name {
+        "i8" => (i8::MIN.into(), i8::MAX.into()),
+        "i16" => (i16::MIN.into(), i16::MAX.into()),
+        "i32" => (i32::MIN.into(), i32::MAX.into()),
+        "i64" | "isize" => (i64::MIN.into(), i64::MAX.into()),
+        "i128" => (i128::MIN, i128::MAX),
+        "u8" => (0, u8::MAX.into()),
+        "u16" => (0, u16::MAX.into()),
+        "u32" => (0, u32::MAX.into()),
+        "u64" | "usize" => (0, u64::MAX.into()),
+        "u128" => (0, i128::MAX),
+        _ => return None,
+    })
+}
+
+fn fmt_type(ty: &TypeExpr) -> String {
+    match ty {
+        TypeExpr::Primitive(name) | TypeExpr::Named(name) => name.clone(),
+        TypeExpr::Date => "date".to_string(),
+        TypeExpr::DateTime => "date-time".to_string(),
+        TypeExpr::Value => "any value".to_string(),
+        TypeExpr::Option(inner) => format!("nullable {}", fmt_type(inner)),
+        TypeExpr::Vec(inner) => format!("array of {}", fmt_type(inner)),
+        TypeExpr::Set(inner) => format!("set of {}", fmt_type(inner)),
+        TypeExpr::Map(inner) => format!("map of {}", fmt_type(inner)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // each change as kind, path and message, for comparing type.rs files that differ in one way
+    fn get_changes(old_rs: &str, new_rs: &str) -> Vec<(&'static str, String, String)> {
+        let schema_of = |type_rs: &str| TypeFile::parse(type_rs).unwrap().to_schema().unwrap();
+        compare_schemas(&schema_of(old_rs), &schema_of(new_rs))
+            .unwrap()
+            .into_iter()
+            .map(|change| {
+                let kind_str = match change.kind {
+                    ChangeKind::Compatible => "compatible",
+                    ChangeKind::Breaking => "breaking",
+                };
+                (kind_str, change.path, change.message)
+            })
+            .collect()
+    }
+
+    fn datum_rs(fields: &str) -> String {
+        format!("pub struct Datum {{\n{fields}\n}}\n")
+    }
+
+    fn change(kind_str: &'static str, path: &str, message: &str) -> (&'static str, String, String) {
+        (kind_str, path.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn removed_and_new_fields() {
+        let old_rs = datum_rs("pub name: String,\npub age: u8,");
+        assert_eq!(
+            get_changes(&old_rs, &datum_rs("pub name: String,")),
+            vec![change("breaking", "Datum.age", "removed field")]
+        );
+        assert_eq!(
+            get_changes(
+                &old_rs,
+                &datum_rs("pub name: String,\npub age: u8,\npub city: String,")
+            ),
+            vec![change("breaking", "Datum.city", "new required field")]
+        );
+        assert_eq!(
+            get_changes(
+                &old_rs,
+                &datum_rs(
+                    "pub name: String,\npub age: u8,\npub city: Option<String>,\n#[serde(default)]\npub score: u8,"
+                )
+            ),
+            vec![
+                change("compatible", "Datum.city", "new optional field"),
+                change("compatible", "Datum.score", "new optional field"),
+            ]
+        );
+    }
+
+    #[test]
+    fn narrowed_types_break_and_widened_ones_dont() {
+        let type_change = |old_ty: &str, new_ty: &str| {
+            get_changes(
+                &datum_rs(&format!("pub value: {old_ty},")),
+                &datum_rs(&format!("pub value: {new_ty},")),
+            )
+        };
+        assert_eq!(
+            type_change("u16", "u8"),
+            vec![change(
+                "breaking",
+                "Datum.value",
+                "changed type from u16 to u8"
+            )]
+        );
+        assert_eq!(
+            type_change("u8", "i8"),
+            vec![change(
+                "breaking",
+                "Datum.value",
+                "changed type from u8 to i8"
+            )]
+        );
+        assert_eq!(
+            type_change("i32", "i64"),
+            vec![change(
+                "compatible",
+                "Datum.value",
+                "changed type from i32 to i64"
+            )]
+        );
+        assert_eq!(
+            type_change("i32", "f64"),
+            vec![change(
+                "compatible",
+                "Datum.value",
+                "changed type from i32 to f64"
+            )]
+        );
+        assert_eq!(
+            type_change("Vec<String>", "HashSet<String>"),
+            vec![change(
+                "breaking",
+                "Datum.value",
+                "items now need to be unique"
+            )]
+        );
+    }
+
+    #[test]
+    fn enum_values_removed_break_and_added_ones_dont() {
+        let size_rs = |variants: &str| {
+            format!(
+                "{}pub enum Size {{ {variants} }}\n",
+                datum_rs("pub size: Size,")
+            )
+        };
+        assert_eq!(
+            get_changes(&size_rs("Small, Large"), &size_rs("Small")),
+            vec![change(
+                "breaking",
+                "Datum.size",
+                r#"removed enum value "Large""#
+            )]
+        );
+        assert_eq!(
+            get_changes(&size_rs("Small"), &size_rs("Small, Large")),
+            vec![change(
+                "compatible",
+                "Datum.size",
+                r#"new enum value "Large""#
+            )]
+        );
+    }
+
+    #[test]
+    fn allowing_unknown_fields_is_compatible() {
+        let datum = datum_rs("pub name: String,");
+        let denying = format!("#[serde(deny_unknown_fields)]\n{datum}");
+        assert_eq!(
+            get_changes(&datum, &denying),
+            vec![change("breaking", "Datum", "now denies unknown fields")]
+        );
+        assert_eq!(
+            get_changes(&denying, &datum),
+            vec![change("compatible", "Datum", "now allows unknown fields")]
+        );
+    }
+
+    #[test]
+    fn int_ranges_contain_narrower_ones() {
+        let primitive = |name: &str| TypeExpr::Primitive(name.to_string());
+        assert!(is_widened(&primitive("u8"), &primitive("u16")));
+        assert!(is_widened(&primitive("u32"), &primitive("i64")));
+        assert!(!is_widened(&primitive("i8"), &primitive("u64")));
+        assert!(!is_widened(&primitive("f64"), &primitive("i64")));
+        assert!(is_widened(&primitive("char"), &primitive("String")));
+        assert!(!is_widened(&primitive("String"), &primitive("char")));
+        assert_eq!(int_range("i8"), Some((-128, 127)));
+        assert_eq!(int_range("String"), None);
+    }
+}
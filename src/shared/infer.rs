@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value;
+
+use super::type_rs::{to_type_ident, EnumDef, FieldDef, StructDef, TypeDef, TypeExpr, TypeFile};
+
+const ROOT_STRUCT: &str = "Datum";
+// names type.rs reads as something other than a defined type
+const RESERVED_NAMES: [&str; 13] = [
+    "BTreeMap",
+    "BTreeSet",
+    "Box",
+    "DateTime",
+    "HashMap",
+    "HashSet",
+    "NaiveDate",
+    "Option",
+    "String",
+    "Utc",
+    "Value",
+    "Vec",
+    "VecDeque",
+];
+// strings become an enum when there are few distinct values, each used more than once
+const ENUM_MAX_VARIANTS: usize = 12;
+const ENUM_MIN_USES: usize = 2;
+const ENUM_MAX_LEN: usize = 40;
+// a field in at least this share of records is likely meant to be in every one
+const MOSTLY_PRESENT: f64 = 0.9;
+// dates serde can't read as NaiveDate are left as strings, with the format documented
+const OTHER_DATE_FMTS: [&str; 5] = ["%Y/%m/%d", "%m/%d/%Y", "%d/%m/%Y", "%d.%m.%Y", "%b %d, %Y"];
+
+// the types a JSON array of objects fits, to bootstrap type.rs and schema.json
+pub fn infer_type_file(data: &Value) -> Result<TypeFile, String> {
+    let records = data
+        .as_array()
+        .ok_or("data should be a JSON array".to_string())?;
+    let objects: Vec<&serde_json::Map<String, Value>> = records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            record
+                .as_object()
+                .ok_or(format!("record {index} should be an object"))
+        })
+        .collect::<Result<_, String>>()?;
+
+    let mut inferrer = Inferrer { defs: Vec::new() };
+    inferrer.infer_struct(ROOT_STRUCT.to_string(), &objects);
+    Ok(TypeFile {
+        root: ROOT_STRUCT.to_string(),
+        defs: inferrer.defs,
+    })
+}
+
+struct Inferrer {
+    defs: Vec<TypeDef>,
+}
+
+impl Inferrer {
+    fn infer_struct(&mut self, name: String, objects: &[&serde_json::Map<String, Value>]) {
+        // reserve the name before nested types are named after fields
+        let def_index = self.defs.len();
+        self.defs.push(TypeDef::Struct(StructDef {
+            name: name.clone(),
+            doc: None,
+            fields: Vec::new(),
+            deny_unknown_fields: false,
+        }));
+
+        // every key used by any record
+        let keys: BTreeSet<&String> = objects.iter().flat_map(|object| object.keys()).collect();
+
+        let mut fields: Vec<FieldDef> = Vec::new();
+        for key in keys {
+            let values: Vec<&Value> = objects
+                .iter()
+                .filter_map(|object| object.get(key))
+                .filter(|value| !value.is_null())
+                .collect();
+            let (ty, mut doc) = self.infer_type(key, &values);
+            // a key missing or null in any record is optional
+            let ty = if values.len() < objects.len() {
+                TypeExpr::Option(Box::new(ty))
+            } else {
+                ty
+            };
+            // which may be a gap in the data, so it's noted for whoever reviews type.rs
+            let missing_count = objects.len() - values.len();
+            if missing_count > 0 && values.len() as f64 >= objects.len() as f64 * MOSTLY_PRESENT {
+                let missing_doc = format!(
+                    "Missing or null in {missing_count} of {} records",
+                    objects.len()
+                );
+                doc = Some(match doc {
+                    Some(doc) => format!("{doc}; {missing_doc}"),
+                    None => missing_doc,
+                });
+            }
+            fields.push(FieldDef {
+                name: key.clone(),
+                doc,
+                ty,
+                has_default: false,
+            });
+        }
+        if let TypeDef::Struct(struct_def) = &mut self.defs[def_index] {
+            struct_def.fields = fields;
+        }
+    }
+
+    // the type fitting every value, with a doc for what the type can't say
+    fn infer_type(&mut self, key: &str, values: &[&Value]) -> (TypeExpr, Option<String>) {
+        if values.is_empty() {
+            return (TypeExpr::Value, None);
+        }
+        if values.iter().all(|value| value.is_boolean()) {
+            return (TypeExpr::Primitive("bool".to_string()), None);
+        }
+        if values.iter().all(|value| value.is_i64()) {
+            return (TypeExpr::Primitive("i64".to_string()), None);
+        }
+        if values.iter().all(|value| value.is_number()) {
+            return (TypeExpr::Primitive("f64".to_string()), None);
+        }
+        if let Some(strs) = values
+            .iter()
+            .map(|value| value.as_str())
+            .collect::<Option<Vec<&str>>>()
+        {
+            return self.infer_string_type(key, &strs);
+        }
+        if values.iter().all(|value| value.is_array()) {
+            let items: Vec<&Value> = values
+                .iter()
+                .filter_map(|value| value.as_array())
+                .flatten()
+                .filter(|item| !item.is_null())
+                .collect();
+            let (item_ty, doc) = self.infer_type(key, &items);
+            return (TypeExpr::Vec(Box::new(item_ty)), doc);
+        }
+        if values.iter().all(|value| value.is_object()) {
+            let objects: Vec<&serde_json::Map<String, Value>> = values
+                .iter()
+                .filter_map(|value| value.as_object())
+                .collect();
+            let name = self.unique_name(key);
+            self.infer_struct(name.clone(), &objects);
+            return (TypeExpr::Named(name), None);
+        }
+        // mixed types can't be described more precisely than any JSON value
+        (TypeExpr::Value, None)
+    }
+
+    fn infer_string_type(&mut self, key: &str, strs: &[&str]) -> (TypeExpr, Option<String>) {
+        let string_ty = TypeExpr::Primitive("String".to_string());
+        if strs
+            .iter()
+            .all(|str| chrono::NaiveDate::parse_from_str(str, "%Y-%m-%d").is_ok())
+        {
+            return (TypeExpr::Date, None);
+        }
+        if strs
+            .iter()
+            .all(|str| chrono::DateTime::parse_from_rfc3339(str).is_ok())
+        {
+            return (TypeExpr::DateTime, None);
+        }
+        for date_fmt in OTHER_DATE_FMTS {
+            if strs
+                .iter()
+                .all(|str| chrono::NaiveDate::parse_from_str(str, date_fmt).is_ok())
+            {
+                return (string_ty, Some(format!("Date formatted as {date_fmt}")));
+            }
+        }
+
+        // like names, which repeat now and then but aren't a fixed set
+        let mut uses: BTreeMap<&str, usize> = BTreeMap::new();
+        for str in strs {
+            *uses.entry(str).or_default() += 1;
+        }
+        let is_enum = uses.len() <= ENUM_MAX_VARIANTS
+            && uses.iter().all(|(variant, count)| {
+                *count >= ENUM_MIN_USES && !variant.is_empty() && variant.len() <= ENUM_MAX_LEN
+            });
+        if !is_enum {
+            return (string_ty, None);
+        }
+        let name = self.unique_name(key);
+        self.defs.push(TypeDef::Enum(EnumDef {
+            name: name.clone(),
+            doc: None,
+            variants: uses.keys().map(|variant| variant.to_string()).collect(),
+        }));
+        (TypeExpr::Named(name), None)
+    }
+
+    // types are named after their field, numbered if the name is taken
+    fn unique_name(&self, key: &str) -> String {
+        let base = to_type_ident(key);
+        let is_taken = |name: &str| {
+            RESERVED_NAMES.contains(&name) || self.defs.iter().any(|def| def.name() == name)
+        };
+        let mut name = base.clone();
+        let mut count = 2;
+        while is_taken(&name) {
+            name = format!("{base}{count}");
+            count += 1;
+        }
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn get_field<'a>(type_file: &'a TypeFile, name: &str) -> &'a FieldDef {
+        let Some(TypeDef::Struct(struct_def)) = type_file.defs.first() else {
+            panic!("Should infer a struct first");
+        };
+        struct_def
+            .fields
+            .iter()
+            .find(|field| field.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn mostly_present_fields_are_noted() {
+        let mut records: Vec<Value> = (0..10).map(|id| json!({ "id": id, "note": "a" })).collect();
+        records[3] = json!({ "id": 3 });
+        records[4] = json!({ "note": "b" });
+        let type_file = infer_type_file(&Value::Array(records)).unwrap();
+        let id_field = get_field(&type_file, "id");
+        assert!(matches!(id_field.ty, TypeExpr::Option(_)));
+        assert_eq!(
+            id_field.doc.as_deref(),
+            Some("Missing or null in 1 of 10 records")
+        );
+        // missing from a fifth of records is likely on purpose
+        let mut records: Vec<Value> = (0..10).map(|id| json!({ "id": id })).collect();
+        records[0] = json!({ "id": 0, "note": "a" });
+        let type_file = infer_type_file(&Value::Array(records)).unwrap();
+        assert_eq!(get_field(&type_file, "note").doc, None);
+    }
+
+    #[test]
+    fn enums_need_every_value_repeated() {
+        let records = json!([
+            { "name": "Ann", "size": "small" },
+            { "name": "Ann", "size": "large" },
+            { "name": "Bo", "size": "small" },
+            { "name": "Bo", "size": "large" },
+            { "name": "Cy", "size": "small" },
+            { "name": "Di", "size": "large" },
+        ]);
+        let type_file = infer_type_file(&records).unwrap();
+        assert!(matches!(
+            &get_field(&type_file, "name").ty,
+            TypeExpr::Primitive(ty) if ty == "String"
+        ));
+        assert!(matches!(
+            get_field(&type_file, "size").ty,
+            TypeExpr::Named(_)
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+use super::{data::DataFile, errors::RunningErrors};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn from_str(severity_str: &str) -> Option<Self> {
+        match severity_str {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            _ => None,
+        }
+    }
+}
+
+// a problem with the data, located by dataset, record index and field where known
+pub struct Issue {
+    pub severity: Severity,
+    pub dataset: Option<String>,
+    // only for data split across files, where index is within the file
+    pub file: Option<String>,
+    pub index: Option<usize>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl Issue {
+    pub fn error(index: Option<usize>, field: Option<String>, message: String) -> Self {
+        Issue {
+            severity: Severity::Error,
+            dataset: None,
+            file: None,
+            index,
+            field,
+            message,
+        }
+    }
+
+    // from a jsonschema instance path like /3/name
+    pub fn from_pointer(severity: Severity, pointer: &str, message: String) -> Self {
+        let mut segments = pointer.split('/').filter(|segment| !segment.is_empty());
+        let mut index = None;
+        let mut field_segments: Vec<&str> = Vec::new();
+        if let Some(first) = segments.next() {
+            match first.parse::<usize>() {
+                Ok(first_index) => index = Some(first_index),
+                Err(_) => field_segments.push(first),
+            }
+        }
+        field_segments.extend(segments);
+        let field = if field_segments.is_empty() {
+            None
+        } else {
+            Some(field_segments.join("."))
+        };
+        Issue {
+            severity,
+            dataset: None,
+            file: None,
+            index,
+            field,
+            message,
+        }
+    }
+
+    // scripts may return a message, or a map of message, severity, dataset, field and index
+    pub fn from_script(value: &rhai::Dynamic, default_index: Option<usize>) -> Self {
+        if let Some(map) = value.read_lock::<rhai::Map>() {
+            let get_str = |key: &str| {
+                map.get(key)
+                    .filter(|value| !value.is_unit())
+                    .map(|value| value.to_string())
+            };
+            let severity = get_str("severity")
+                .and_then(|severity_str| Severity::from_str(severity_str.as_str()))
+                .unwrap_or(Severity::Error);
+            let index = map
+                .get("index")
+                .and_then(|index| index.as_int().ok())
+                .and_then(|index| usize::try_from(index).ok())
+                .or(default_index);
+            return Issue {
+                severity,
+                dataset: get_str("dataset"),
+                file: None,
+                index,
+                field: get_str("field"),
+                message: get_str("message").unwrap_or_else(|| format!("{:?}", *map)),
+            };
+        }
+        Issue::error(default_index, None, value.to_string())
+    }
+
+    // issues found within one dataset of a workspace don't know its name
+    pub fn in_dataset(mut self, dataset: &Option<String>) -> Self {
+        if self.dataset.is_none() {
+            self.dataset = dataset.clone();
+        }
+        self
+    }
+
+    // records of split data are found by file, so indexes across every file are no help
+    pub fn in_data_file(mut self, files: &[DataFile]) -> Self {
+        let Some(index) = self.index else {
+            return self;
+        };
+        let mut start = 0;
+        for file in files {
+            if index < start + file.len {
+                self.file = Some(file.path.to_string_lossy().to_string());
+                self.index = if file.is_record {
+                    None
+                } else {
+                    Some(index - start)
+                };
+                break;
+            }
+            start += file.len;
+        }
+        self
+    }
+
+    pub fn fmt_location(&self) -> String {
+        let mut location: Vec<String> = Vec::new();
+        if let Some(dataset) = &self.dataset {
+            location.push(format!("dataset {dataset}"));
+        }
+        if let Some(file) = &self.file {
+            location.push(format!("file {file}"));
+        }
+        if let Some(index) = self.index {
+            location.push(format!("record {index}"));
+        }
+        if let Some(field) = &self.field {
+            location.push(format!("field {field}"));
+        }
+        if location.is_empty() {
+            String::new()
+        } else {
+            format!("{}: ", location.join(", "))
+        }
+    }
+}
+
+pub fn add_issues(running_errors: &mut RunningErrors, issue_type: &str, issues: &[Issue]) {
+    let err_type = format!("{issue_type} error");
+    let warn_type = format!("{issue_type} warning");
+    for issue in issues {
+        let details = format!("{}{}", issue.fmt_location(), issue.message);
+        match issue.severity {
+            Severity::Error => running_errors.add_err(&err_type, details),
+            Severity::Warning => running_errors.add_warn(&warn_type, details),
+        }
+    }
+}
+
+// prints every issue, then stops if any of them are errors
+pub fn assert_no_errors(issue_type: &str, issues: &[Issue]) {
+    let mut running_errors = RunningErrors::new();
+    add_issues(&mut running_errors, issue_type, issues);
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        panic!();
+    }
+}
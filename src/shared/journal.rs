@@ -0,0 +1,88 @@
+use std::path::{Path, PathBuf};
+
+use super::{config::Config, path::style_path};
+
+pub const JOURNAL_FILE_NAME: &str = "journal.json";
+
+// which snapshot in the backups directory was made by which migration
+#[allow(non_snake_case)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct JournalEntry {
+    pub migratedAt: String,
+    pub dataset: Option<String>,
+    // file name in the backups directory, of the snapshot of the project before the migration
+    pub snapshot: String,
+}
+
+impl JournalEntry {
+    pub fn new(config: &Config, snapshot_directory: &Path) -> Self {
+        JournalEntry {
+            migratedAt: chrono::Local::now().to_rfc3339(),
+            dataset: config.name.clone(),
+            snapshot: snapshot_directory
+                .file_name()
+                .expect(
+                    format!(
+                        "Should get name of {}",
+                        style_path(snapshot_directory, "snapshot")
+                    )
+                    .as_str(),
+                )
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+}
+
+fn get_journal_path(backups_directory: &Path) -> PathBuf {
+    backups_directory.join(JOURNAL_FILE_NAME)
+}
+
+fn read_journal(backups_directory: &Path) -> Vec<JournalEntry> {
+    let journal_path = get_journal_path(backups_directory);
+    if !journal_path.is_file() {
+        return Vec::new();
+    }
+    let journal_str = std::fs::read_to_string(&journal_path)
+        .expect(format!("Should read {}", style_path(&journal_path, "journal")).as_str());
+    serde_json::from_str(&journal_str).expect(
+        format!(
+            "{} should be a list of migrations",
+            style_path(&journal_path, "journal")
+        )
+        .as_str(),
+    )
+}
+
+fn write_journal(backups_directory: &Path, journal: &[JournalEntry]) {
+    let journal_path = get_journal_path(backups_directory);
+    let journal_str = serde_json::to_string_pretty(journal).expect("turned journal to string");
+    std::fs::write(&journal_path, journal_str)
+        .expect(format!("Should write {}", style_path(&journal_path, "journal")).as_str());
+}
+
+pub fn push_entry(backups_directory: &Path, entry: JournalEntry) {
+    let mut journal = read_journal(backups_directory);
+    journal.push(entry);
+    write_journal(backups_directory, &journal);
+}
+
+// the last migration of the dataset, and the one before it, whose snapshot has the older type file
+// left in the journal, so an undo that fails can be retried
+pub fn last_entry(config: &Config) -> Option<(JournalEntry, Option<JournalEntry>)> {
+    let journal = read_journal(&config.backups_directory);
+    let is_dataset = |entry: &JournalEntry| entry.dataset == config.name;
+    let last_index = journal.iter().rposition(is_dataset)?;
+    let previous_entry = journal[..last_index]
+        .iter()
+        .rfind(|entry| is_dataset(entry))
+        .cloned();
+    Some((journal[last_index].clone(), previous_entry))
+}
+
+// once the migration has been undone
+pub fn remove_entry(config: &Config, entry: &JournalEntry) {
+    let mut journal = read_journal(&config.backups_directory);
+    journal.retain(|other| other.dataset != entry.dataset || other.snapshot != entry.snapshot);
+    write_journal(&config.backups_directory, &journal);
+}
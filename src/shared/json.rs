@@ -1,43 +1,71 @@
 use std::path::Path;
 
-use crate::shared::errors::RunningErrors;
+use super::{
+    issues::{assert_no_errors, Issue, Severity},
+    path::style_path,
+};
 
-use super::path::style_path;
+pub fn read_json(path: &Path) -> serde_json::Value {
+    try_read_json(path).unwrap_or_else(|err| panic!("{err}"))
+}
 
-fn read_json(path: &Path) -> serde_json::Value {
+pub fn try_read_json(path: &Path) -> Result<serde_json::Value, String> {
     // reading into memory is faster than parsing via stream
     // https://github.com/serde-rs/json/issues/160
-    let str_res = std::fs::read_to_string(path);
-    let str = str_res.expect(format!("Should read {}", style_path(path, "JSON")).as_str());
-    let json_res = serde_json::from_str(str.as_str());
-    json_res.expect(format!("Should parse {} as JSON", style_path(path, "file")).as_str())
+    let str = std::fs::read_to_string(path)
+        .map_err(|err| format!("Should read {}: {err}", style_path(path, "JSON")))?;
+    serde_json::from_str(str.as_str())
+        .map_err(|err| format!("Should parse {} as JSON: {err}", style_path(path, "file")))
+}
+
+pub fn get_schema_issues(instance_json: &serde_json::Value, schema_path: &Path) -> Vec<Issue> {
+    let schema_json = read_json(schema_path);
+    try_get_schema_issues(instance_json, &schema_json).unwrap_or_else(|err| {
+        panic!(
+            "{} should be a valid JSON schema: {err}",
+            style_path(schema_path, "schema")
+        )
+    })
+}
+
+pub fn try_get_schema_issues(
+    instance_json: &serde_json::Value,
+    schema_json: &serde_json::Value,
+) -> Result<Vec<Issue>, String> {
+    let schema = jsonschema::JSONSchema::options()
+        .compile(schema_json)
+        .map_err(|err| err.to_string())?;
+    let validate_res = schema.validate(instance_json);
+    Ok(match validate_res {
+        Ok(_) => Vec::new(),
+        Err(errors) => errors
+            .map(|error| {
+                Issue::from_pointer(
+                    Severity::Error,
+                    error.instance_path.to_string().as_str(),
+                    error.to_string(),
+                )
+            })
+            .collect(),
+    })
 }
 
 pub fn validate_json<Instance: for<'a> serde::Deserialize<'a>>(
     instance_path: &Path,
     schema_path: &Path,
+) -> Instance {
+    validate_json_with_schema(instance_path, &read_json(schema_path))
+}
+
+// for schemas that aren't files, like those for heda's own config
+pub fn validate_json_with_schema<Instance: for<'a> serde::Deserialize<'a>>(
+    instance_path: &Path,
+    schema_json: &serde_json::Value,
 ) -> Instance {
     let instance_json = read_json(instance_path);
-    let schema_json = read_json(schema_path);
-    let schema = jsonschema::JSONSchema::options()
-        .compile(&schema_json)
-        .expect(
-            format!(
-                "{} should be a valid JSON schema",
-                style_path(schema_path, "schema")
-            )
-            .as_str(),
-        );
-    let validate_res = schema.validate(&instance_json);
-    if let Err(errors) = validate_res {
-        let err_type = "Validation error".to_string();
-        let mut running_errors = RunningErrors::new();
-        for error in errors {
-            running_errors.add_err(&err_type, error.to_string())
-        }
-        running_errors.print_errs();
-        panic!();
-    }
+    let issues = try_get_schema_issues(&instance_json, schema_json)
+        .unwrap_or_else(|err| panic!("Schema should be a valid JSON schema: {err}"));
+    assert_no_errors("Validation", &issues);
 
     let instance_res = serde_json::from_value::<Instance>(instance_json.clone());
     instance_res.expect(
@@ -0,0 +1,300 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde_json::Value;
+
+use super::{
+    config::Config,
+    datum::Datum,
+    issues::{Issue, Severity},
+};
+
+// referential integrity across datasets, which JSON schema can't express
+// references to datasets that aren't loaded, like with --dataset, are not checked
+pub fn get_key_issues(datasets: &[(&Config, &[Datum])]) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = Vec::new();
+
+    // primary key values of each dataset, as JSON strings so 1 and "1" stay distinct
+    let mut primary_keys: BTreeMap<Option<&String>, HashSet<String>> = BTreeMap::new();
+    for (config, data) in datasets {
+        let Some(primary_key) = &config.primary_key else {
+            continue;
+        };
+        let mut first_indexes: HashMap<String, usize> = HashMap::new();
+        for (index, datum) in data.iter().enumerate() {
+            match datum.get(primary_key) {
+                None | Some(Value::Null) => issues.push(
+                    Issue::error(
+                        Some(index),
+                        Some(primary_key.clone()),
+                        "Missing primary key".to_string(),
+                    )
+                    .in_dataset(&config.name),
+                ),
+                Some(value) => {
+                    let key = value.to_string();
+                    match first_indexes.get(&key) {
+                        Some(first_index) => issues.push(
+                            Issue::error(
+                                Some(index),
+                                Some(primary_key.clone()),
+                                format!(
+                                    "Duplicate primary key {key}, first used by record {first_index}"
+                                ),
+                            )
+                            .in_dataset(&config.name),
+                        ),
+                        None => {
+                            first_indexes.insert(key, index);
+                        }
+                    }
+                }
+            }
+        }
+        primary_keys.insert(
+            config.name.as_ref(),
+            first_indexes.into_keys().collect::<HashSet<String>>(),
+        );
+    }
+
+    // pasted records usually repeat more than just the primary key
+    for (config, data) in datasets {
+        for fields in &config.unique_keys {
+            for group in get_duplicate_groups(data, fields) {
+                let indexes: Vec<String> = group.iter().map(|index| index.to_string()).collect();
+                issues.push(
+                    Issue::error(
+                        Some(group[0]),
+                        Some(fields.join("+")),
+                        format!("Duplicate records {}", indexes.join(", ")),
+                    )
+                    .in_dataset(&config.name),
+                );
+            }
+        }
+    }
+
+    // which primary keys are referenced, and by which datasets
+    let mut referenced_keys: BTreeMap<&String, HashSet<String>> = BTreeMap::new();
+    let mut referencing_names: BTreeMap<&String, Vec<String>> = BTreeMap::new();
+    for (config, data) in datasets {
+        for (field, target) in &config.foreign_keys {
+            let Some(target_keys) = primary_keys.get(&Some(target)) else {
+                continue;
+            };
+            let referencing = referencing_names.entry(target).or_default();
+            let name = config.name.clone().unwrap_or_default();
+            if !referencing.contains(&name) {
+                referencing.push(name);
+            }
+            for (index, datum) in data.iter().enumerate() {
+                for value in get_references(datum.get(field)) {
+                    let key = value.to_string();
+                    if !target_keys.contains(&key) {
+                        issues.push(
+                            Issue::error(
+                                Some(index),
+                                Some(field.clone()),
+                                format!("Dangling reference {key} to dataset {target}"),
+                            )
+                            .in_dataset(&config.name),
+                        );
+                    }
+                    referenced_keys.entry(target).or_default().insert(key);
+                }
+            }
+        }
+    }
+
+    // records that every referencing dataset ignores are likely left over from a deletion
+    for (config, data) in datasets {
+        let (Some(name), Some(primary_key)) = (&config.name, &config.primary_key) else {
+            continue;
+        };
+        let Some(referencing) = referencing_names.get(name) else {
+            continue;
+        };
+        let referenced = referenced_keys.get(name);
+        for (index, datum) in data.iter().enumerate() {
+            let Some(value) = datum.get(primary_key).filter(|value| !value.is_null()) else {
+                continue;
+            };
+            let key = value.to_string();
+            if !referenced.is_some_and(|referenced| referenced.contains(&key)) {
+                issues.push(Issue {
+                    severity: Severity::Warning,
+                    dataset: config.name.clone(),
+                    file: None,
+                    index: Some(index),
+                    field: Some(primary_key.clone()),
+                    message: format!(
+                        "Orphaned record {key}, not referenced by {}",
+                        referencing.join(", ")
+                    ),
+                });
+            }
+        }
+    }
+    issues
+}
+
+// the primary key, then each unique key, as sets of fields which identify a record
+pub fn get_key_sets(config: &Config) -> Vec<Vec<String>> {
+    let mut key_sets: Vec<Vec<String>> = Vec::new();
+    if let Some(primary_key) = &config.primary_key {
+        key_sets.push(vec![primary_key.clone()]);
+    }
+    key_sets.extend(config.unique_keys.iter().cloned());
+    key_sets
+}
+
+// indexes of records sharing values for every field, in order of first appearance
+// records missing any of the fields aren't compared, like nulls in a SQL unique index
+pub fn get_duplicate_groups(data: &[Datum], fields: &[String]) -> Vec<Vec<usize>> {
+    if fields.is_empty() {
+        return Vec::new();
+    }
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_indexes: HashMap<String, usize> = HashMap::new();
+    for (index, datum) in data.iter().enumerate() {
+        let values: Option<Vec<&Value>> = fields
+            .iter()
+            .map(|field| datum.get(field).filter(|value| !value.is_null()))
+            .collect();
+        let Some(values) = values else {
+            continue;
+        };
+        let key = serde_json::to_string(&values).unwrap_or_default();
+        match group_indexes.get(&key) {
+            Some(group_index) => groups[*group_index].push(index),
+            None => {
+                group_indexes.insert(key, groups.len());
+                groups.push(vec![index]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+// a foreign key field may hold one reference, many, or none
+fn get_references(value_opt: Option<&Value>) -> Vec<&Value> {
+    match value_opt {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(values)) => values.iter().filter(|value| !value.is_null()).collect(),
+        Some(value) => vec![value],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn make_config(name: &str, primary_key: Option<&str>, foreign_keys: &[(&str, &str)]) -> Config {
+        let path: Box<Path> = Path::new(name).into();
+        Config {
+            name: Some(name.to_string()),
+            root_directory: path.clone(),
+            backups_directory: path.clone(),
+            data_json: path.clone(),
+            plots_directory: path.clone(),
+            schema_json: path.clone(),
+            scripts_rhai: path.clone(),
+            type_rs: path,
+            primary_key: primary_key.map(str::to_string),
+            foreign_keys: foreign_keys
+                .iter()
+                .map(|(field, target)| (field.to_string(), target.to_string()))
+                .collect(),
+            unique_keys: Vec::new(),
+        }
+    }
+
+    fn to_data(records: Value) -> Vec<Datum> {
+        serde_json::from_value(records).unwrap()
+    }
+
+    fn fmt_issues(issues: &[Issue]) -> Vec<String> {
+        issues
+            .iter()
+            .map(|issue| format!("{}{}", issue.fmt_location(), issue.message))
+            .collect()
+    }
+
+    #[test]
+    fn primary_keys_are_present_and_unique() {
+        let config = make_config("people", Some("id"), &[]);
+        // "1" and 1 are different keys
+        let data = to_data(json!([{ "id": 1 }, { "id": "1" }, {}, { "id": 1 }]));
+        assert_eq!(
+            fmt_issues(&get_key_issues(&[(&config, &data)])),
+            vec![
+                "dataset people, record 2, field id: Missing primary key",
+                "dataset people, record 3, field id: Duplicate primary key 1, first used by record 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn foreign_keys_reference_loaded_datasets() {
+        let people = make_config("people", Some("id"), &[]);
+        let pets = make_config("pets", Some("id"), &[("owner", "people"), ("vet", "vets")]);
+        let people_data = to_data(json!([{ "id": 1 }, { "id": 2 }]));
+        // vets aren't loaded, so references to them can't be checked
+        let pet_data = to_data(json!([
+            { "id": "a", "owner": 1, "vet": 9 },
+            { "id": "b", "owner": [2, 3, null] },
+            { "id": "c", "owner": null }
+        ]));
+        assert_eq!(
+            fmt_issues(&get_key_issues(&[
+                (&people, &people_data),
+                (&pets, &pet_data)
+            ])),
+            vec!["dataset pets, record 1, field owner: Dangling reference 3 to dataset people"]
+        );
+    }
+
+    #[test]
+    fn unique_keys_group_records_with_every_field() {
+        let mut config = make_config("people", None, &[]);
+        config.unique_keys = vec![vec!["first".to_string(), "last".to_string()]];
+        let data = to_data(json!([
+            { "first": "a", "last": "b" },
+            { "first": "a", "last": null },
+            { "first": "a", "last": "b" },
+            { "first": "a" },
+        ]));
+        assert_eq!(
+            get_duplicate_groups(&data, &config.unique_keys[0]),
+            vec![vec![0, 2]]
+        );
+        assert_eq!(
+            fmt_issues(&get_key_issues(&[(&config, &data)])),
+            vec!["dataset people, record 0, field first+last: Duplicate records 0, 2"]
+        );
+    }
+
+    #[test]
+    fn unreferenced_records_of_referenced_datasets_are_orphans() {
+        let people = make_config("people", Some("id"), &[]);
+        let pets = make_config("pets", Some("id"), &[("owner", "people")]);
+        let people_data = to_data(json!([{ "id": 1 }, { "id": 2 }, { "id": "1" }]));
+        let pet_data = to_data(json!([{ "id": "a", "owner": 1 }]));
+        let issues = get_key_issues(&[(&people, &people_data), (&pets, &pet_data)]);
+        // pets aren't referenced by anything, so none of them are orphans
+        assert_eq!(
+            fmt_issues(&issues),
+            vec![
+                "dataset people, record 1, field id: Orphaned record 2, not referenced by pets",
+                r#"dataset people, record 2, field id: Orphaned record "1", not referenced by pets"#,
+            ]
+        );
+        assert!(issues
+            .iter()
+            .all(|issue| issue.severity == Severity::Warning));
+    }
+}
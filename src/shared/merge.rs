@@ -0,0 +1,285 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{Map, Value};
+
+use super::{datum::Datum, diff::get_keys, issues::Issue};
+
+// marks a value both sides changed differently, keeping every side so it can be resolved by hand
+pub const CONFLICT_KEY: &str = "$conflict";
+
+// records are matched by primary key, then merged field by field
+pub fn merge_data(
+    base_data: &[Datum],
+    our_data: &[Datum],
+    their_data: &[Datum],
+    primary_key: Option<&str>,
+) -> (Vec<Datum>, usize) {
+    let by_key = |data: &[Datum]| -> HashMap<String, Value> {
+        get_keys(data, primary_key)
+            .into_iter()
+            .zip(data.iter().map(|datum| Value::Object(datum.clone())))
+            .collect()
+    };
+    let (base_by_key, our_by_key, their_by_key) =
+        (by_key(base_data), by_key(our_data), by_key(their_data));
+
+    // our order is kept, with records only they have after ours
+    let mut keys = get_keys(our_data, primary_key);
+    let our_keys: HashSet<String> = keys.iter().cloned().collect();
+    keys.extend(
+        get_keys(their_data, primary_key)
+            .into_iter()
+            .filter(|key| !our_keys.contains(key)),
+    );
+
+    let mut conflict_count = 0;
+    let mut merged_data: Vec<Datum> = Vec::new();
+    for key in keys {
+        let merged_opt = merge_values(
+            base_by_key.get(&key),
+            our_by_key.get(&key),
+            their_by_key.get(&key),
+            &mut conflict_count,
+        );
+        if let Some(Value::Object(mut merged_datum)) = merged_opt {
+            // conflicts over a whole record keep its key, so it can still be found by it
+            if let (true, Some(field)) = (merged_datum.contains_key(CONFLICT_KEY), primary_key) {
+                let key_value = [&our_by_key, &their_by_key, &base_by_key]
+                    .iter()
+                    .find_map(|side_by_key| side_by_key.get(&key)?.get(field));
+                if let Some(key_value) = key_value {
+                    merged_datum.insert(field.to_string(), key_value.clone());
+                }
+            }
+            merged_data.push(merged_datum);
+        }
+    }
+    (merged_data, conflict_count)
+}
+
+// None is a missing value, like a record one side deleted
+fn merge_values(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflict_count: &mut usize,
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+    if let (Some(Value::Object(our_object)), Some(Value::Object(their_object))) = (ours, theirs) {
+        // objects added on both sides merge as if they started empty
+        let empty_object = Map::new();
+        let base_object = match base {
+            Some(Value::Object(base_object)) => base_object,
+            _ => &empty_object,
+        };
+        let mut fields: Vec<&String> = our_object.keys().collect();
+        fields.extend(
+            their_object
+                .keys()
+                .filter(|field| !our_object.contains_key(*field)),
+        );
+        let mut merged_object = Map::new();
+        for field in fields {
+            let merged_opt = merge_values(
+                base_object.get(field),
+                our_object.get(field),
+                their_object.get(field),
+                conflict_count,
+            );
+            if let Some(merged) = merged_opt {
+                merged_object.insert(field.clone(), merged);
+            }
+        }
+        return Some(Value::Object(merged_object));
+    }
+
+    *conflict_count += 1;
+    let mut sides = Map::new();
+    for (side, value_opt) in [("base", base), ("ours", ours), ("theirs", theirs)] {
+        if let Some(value) = value_opt {
+            sides.insert(side.to_string(), value.clone());
+        }
+    }
+    let mut conflict = Map::new();
+    conflict.insert(CONFLICT_KEY.to_string(), Value::Object(sides));
+    Some(Value::Object(conflict))
+}
+
+// every conflict left by heda merge-driver, as an error at its record and field
+pub fn get_conflict_issues(data_json: &Value) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = Vec::new();
+    if let Value::Array(records) = data_json {
+        for (index, record) in records.iter().enumerate() {
+            find_conflicts(record, index, None, &mut issues);
+        }
+    }
+    issues
+}
+
+// a conflict stands in for a value, so schema issues at or within it add nothing
+// issues elsewhere in a conflicted record are still real
+pub fn without_conflicted(issues: Vec<Issue>, conflict_issues: &[Issue]) -> Vec<Issue> {
+    let is_conflicted = |issue: &Issue| {
+        conflict_issues.iter().any(|conflict| {
+            conflict.index == issue.index
+                && match (&conflict.field, &issue.field) {
+                    (None, _) => true,
+                    (Some(_), None) => false,
+                    (Some(conflict_field), Some(field)) => {
+                        field == conflict_field || field.starts_with(&format!("{conflict_field}."))
+                    }
+                }
+        })
+    };
+    issues
+        .into_iter()
+        .filter(|issue| !is_conflicted(issue))
+        .collect()
+}
+
+fn find_conflicts(value: &Value, index: usize, field: Option<String>, issues: &mut Vec<Issue>) {
+    let Value::Object(object) = value else {
+        return;
+    };
+    if let Some(sides) = object.get(CONFLICT_KEY) {
+        let fmt_side = |side: &str| match sides.get(side) {
+            Some(side_value) => side_value.to_string(),
+            None => "missing".to_string(),
+        };
+        issues.push(Issue::error(
+            Some(index),
+            field,
+            format!(
+                "Merge conflict between ours {} and theirs {}, from base {}",
+                fmt_side("ours"),
+                fmt_side("theirs"),
+                fmt_side("base")
+            ),
+        ));
+        return;
+    }
+    for (key, inner) in object {
+        let inner_field = match &field {
+            Some(field) => format!("{field}.{key}"),
+            None => key.clone(),
+        };
+        find_conflicts(inner, index, Some(inner_field), issues);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn merge(
+        base: Option<Value>,
+        ours: Option<Value>,
+        theirs: Option<Value>,
+    ) -> (Option<Value>, usize) {
+        let mut conflict_count = 0;
+        let merged = merge_values(
+            base.as_ref(),
+            ours.as_ref(),
+            theirs.as_ref(),
+            &mut conflict_count,
+        );
+        (merged, conflict_count)
+    }
+
+    #[test]
+    fn one_sided_changes_are_kept() {
+        let base = json!({ "a": 1, "b": 1 });
+        assert_eq!(
+            merge(
+                Some(base.clone()),
+                Some(json!({ "a": 2, "b": 1 })),
+                Some(base.clone())
+            ),
+            (Some(json!({ "a": 2, "b": 1 })), 0)
+        );
+        assert_eq!(
+            merge(Some(base.clone()), Some(base.clone()), None),
+            (None, 0)
+        );
+        assert_eq!(
+            merge(
+                Some(base.clone()),
+                Some(json!({ "a": 2, "b": 1 })),
+                Some(json!({ "a": 1, "b": 2 }))
+            ),
+            (Some(json!({ "a": 2, "b": 2 })), 0)
+        );
+    }
+
+    #[test]
+    fn objects_added_on_both_sides_merge_by_field() {
+        assert_eq!(
+            merge(
+                None,
+                Some(json!({ "a": 1 })),
+                Some(json!({ "a": 1, "b": 2 }))
+            ),
+            (Some(json!({ "a": 1, "b": 2 })), 0)
+        );
+    }
+
+    #[test]
+    fn different_changes_are_conflicts_with_each_side() {
+        assert_eq!(
+            merge(
+                Some(json!({ "a": 1 })),
+                Some(json!({ "a": 2 })),
+                Some(json!({ "a": 3 }))
+            ),
+            (
+                Some(json!({ "a": { CONFLICT_KEY: { "base": 1, "ours": 2, "theirs": 3 } } })),
+                1
+            )
+        );
+        // a record deleted on one side and edited on the other has no value for that side
+        assert_eq!(
+            merge(Some(json!("x")), None, Some(json!("y"))),
+            (
+                Some(json!({ CONFLICT_KEY: { "base": "x", "theirs": "y" } })),
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn only_issues_within_conflicts_are_dropped() {
+        let data_json = json!([{ "a": { CONFLICT_KEY: {} }, "b": 1 }, { CONFLICT_KEY: {} }]);
+        let conflict_issues = get_conflict_issues(&data_json);
+        let issue = |index: usize, field: Option<&str>| {
+            Issue::error(Some(index), field.map(str::to_string), String::new())
+        };
+        let issues = vec![
+            issue(0, Some("a")),
+            issue(0, Some("a.inner")),
+            issue(0, Some("ab")),
+            issue(0, Some("b")),
+            issue(0, None),
+            issue(1, Some("b")),
+        ];
+        let kept: Vec<(Option<usize>, Option<String>)> =
+            without_conflicted(issues, &conflict_issues)
+                .into_iter()
+                .map(|issue| (issue.index, issue.field))
+                .collect();
+        assert_eq!(
+            kept,
+            vec![
+                (Some(0), Some("ab".to_string())),
+                (Some(0), Some("b".to_string())),
+                (Some(0), None),
+            ]
+        );
+    }
+}
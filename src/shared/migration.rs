@@ -0,0 +1,257 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use super::{
+    datum::{get_field, set_field, take_field, Datum},
+    issues::Issue,
+    json::validate_json_with_schema,
+    schema::get_schema,
+    scripts::{from_dynamic, to_dynamic, RhaiSpace, ScriptFn},
+};
+
+/// # heda migration
+/// Steps applied in order to every item in the data, as an alternative to a migrate script
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MigrationJsonSchema {
+    /// Steps to apply, in order. Fields can be nested with dots, like address.city.
+    pub steps: Vec<MigrationStep>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(tag = "op", rename_all = "camelCase", deny_unknown_fields)]
+pub enum MigrationStep {
+    /// Renames a field, keeping its value. Fails where the new name already has a value.
+    Rename { from: String, to: String },
+    /// Removes a field.
+    Delete { field: String },
+    /// Sets a field where it is missing or null.
+    SetDefault { field: String, value: Value },
+    /// Moves a field into a nested object, like from city to address.city, creating the object if needed. Fails where the new field already has a value.
+    Move { from: String, to: String },
+    /// Converts the value of a field to another type. Missing and null values are left as is.
+    #[serde(rename_all = "camelCase")]
+    Convert {
+        field: String,
+        to: Conversion,
+        /// For dates, the chrono format the value is currently in, like %m/%d/%Y.
+        from_format: Option<String>,
+    },
+    /// Calls the migrate function of the scripts file, for changes the other steps can't make.
+    Script,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Conversion {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    /// Wraps the value in an array, unless it already is one.
+    Array,
+    /// An ISO 8601 date, like 2024-01-31.
+    Date,
+}
+
+pub fn read_migration(path: &Path) -> Vec<MigrationStep> {
+    validate_json_with_schema::<MigrationJsonSchema>(path, &get_schema::<MigrationJsonSchema>())
+        .steps
+}
+
+pub fn has_script_step(steps: &[MigrationStep]) -> bool {
+    steps
+        .iter()
+        .any(|step| matches!(step, MigrationStep::Script))
+}
+
+// every record is attempted, so all failures are reported at once
+pub fn apply_steps(
+    steps: &[MigrationStep],
+    data: &[Datum],
+    rhai_space: &mut RhaiSpace,
+) -> Result<Vec<Datum>, Vec<Issue>> {
+    let mut new_data: Vec<Datum> = Vec::new();
+    let mut issues: Vec<Issue> = Vec::new();
+    for (index, datum) in data.iter().enumerate() {
+        let mut new_datum = datum.clone();
+        for step in steps {
+            let step_res = match step {
+                MigrationStep::Script => rhai_space
+                    .call_fn_at::<rhai::Dynamic>(
+                        ScriptFn::Migrate,
+                        Some(index),
+                        (to_dynamic(&new_datum),),
+                    )
+                    .map_err(|issue| (None, issue.message))
+                    .and_then(|fn_res| {
+                        from_dynamic::<Datum>(&fn_res).map_err(|_| {
+                            (
+                                None,
+                                format!(
+                                    "{} fn should return an object",
+                                    console::style("migrate").magenta()
+                                ),
+                            )
+                        })
+                    })
+                    .map(|script_datum| new_datum = script_datum),
+                _ => apply_step(step, &mut new_datum),
+            };
+            if let Err((field, message)) = step_res {
+                issues.push(Issue::error(Some(index), field, message));
+                break;
+            }
+        }
+        new_data.push(new_datum);
+    }
+    if issues.is_empty() {
+        Ok(new_data)
+    } else {
+        Err(issues)
+    }
+}
+
+// errors are the field, if any, and what went wrong
+fn apply_step(step: &MigrationStep, datum: &mut Datum) -> Result<(), (Option<String>, String)> {
+    match step {
+        MigrationStep::Rename { from, to } | MigrationStep::Move { from, to } => {
+            // both values are likely wanted, so which to keep is left to a script
+            let is_taken = get_field(datum, to).is_some_and(|value| !value.is_null());
+            if is_taken && get_field(datum, from).is_some() && from != to {
+                return Err((
+                    Some(to.clone()),
+                    format!("already has a value, which {from} would overwrite"),
+                ));
+            }
+            if let Some(value) = take_field(datum, from) {
+                set_field(datum, to, value).map_err(|err| (Some(to.clone()), err))?;
+            }
+        }
+        MigrationStep::Delete { field } => {
+            take_field(datum, field);
+        }
+        MigrationStep::SetDefault { field, value } => {
+            if get_field(datum, field).map_or(true, |current| current.is_null()) {
+                set_field(datum, field, value.clone()).map_err(|err| (Some(field.clone()), err))?;
+            }
+        }
+        MigrationStep::Convert {
+            field,
+            to,
+            from_format,
+        } => {
+            if let Some(value) = get_field(datum, field).filter(|value| !value.is_null()) {
+                let converted = convert(value, to, from_format.as_deref())
+                    .map_err(|err| (Some(field.clone()), err))?;
+                set_field(datum, field, converted).map_err(|err| (Some(field.clone()), err))?;
+            }
+        }
+        MigrationStep::Script => {}
+    }
+    Ok(())
+}
+
+fn convert(value: &Value, to: &Conversion, from_format: Option<&str>) -> Result<Value, String> {
+    let fail = |to_str: &str| format!("Could not convert {value} to {to_str}");
+    Ok(match to {
+        Conversion::String => match value {
+            Value::String(_) => value.clone(),
+            Value::Number(number) => Value::String(number.to_string()),
+            Value::Bool(bool_value) => Value::String(bool_value.to_string()),
+            _ => return Err(fail("a string")),
+        },
+        Conversion::Integer => match value {
+            Value::Number(number) if number.is_i64() || number.is_u64() => value.clone(),
+            // out of range floats would saturate, like 1e20 becoming i64::MAX
+            Value::Number(number) => match number.as_f64() {
+                Some(float)
+                    if float.fract() == 0.0
+                        && float >= i64::MIN as f64
+                        && float < -(i64::MIN as f64) =>
+                {
+                    Value::from(float as i64)
+                }
+                _ => return Err(fail("an integer")),
+            },
+            Value::String(str) => {
+                Value::from(str.trim().parse::<i64>().map_err(|_| fail("an integer"))?)
+            }
+            Value::Bool(bool_value) => Value::from(i64::from(*bool_value)),
+            _ => return Err(fail("an integer")),
+        },
+        Conversion::Number => match value {
+            Value::Number(_) => value.clone(),
+            Value::String(str) => {
+                let float = str.trim().parse::<f64>().map_err(|_| fail("a number"))?;
+                serde_json::Number::from_f64(float)
+                    .map(Value::Number)
+                    .ok_or(fail("a number"))?
+            }
+            _ => return Err(fail("a number")),
+        },
+        Conversion::Boolean => match value {
+            Value::Bool(_) => value.clone(),
+            Value::Number(number) => match number.as_f64() {
+                Some(float) if float == 0.0 => Value::Bool(false),
+                Some(float) if float == 1.0 => Value::Bool(true),
+                _ => return Err(fail("a boolean")),
+            },
+            Value::String(str) => match str.trim().to_lowercase().as_str() {
+                "true" | "yes" | "y" | "1" => Value::Bool(true),
+                "false" | "no" | "n" | "0" => Value::Bool(false),
+                _ => return Err(fail("a boolean")),
+            },
+            _ => return Err(fail("a boolean")),
+        },
+        Conversion::Array => match value {
+            Value::Array(_) => value.clone(),
+            _ => Value::Array(vec![value.clone()]),
+        },
+        Conversion::Date => {
+            let str = value.as_str().ok_or(fail("a date"))?;
+            let date =
+                chrono::NaiveDate::parse_from_str(str.trim(), from_format.unwrap_or("%Y-%m-%d"))
+                    .map_err(|_| fail("a date"))?;
+            Value::String(date.format("%Y-%m-%d").to_string())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn to_datum(value: Value) -> Datum {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn integers_are_converted_only_within_range() {
+        assert_eq!(
+            convert(&json!(3.0), &Conversion::Integer, None),
+            Ok(json!(3))
+        );
+        assert!(convert(&json!(1e20), &Conversion::Integer, None).is_err());
+        assert!(convert(&json!(-1e20), &Conversion::Integer, None).is_err());
+        assert!(convert(&json!(9223372036854775808.0), &Conversion::Integer, None).is_err());
+    }
+
+    #[test]
+    fn rename_does_not_overwrite_a_value() {
+        let rename = MigrationStep::Rename {
+            from: "city".to_string(),
+            to: "town".to_string(),
+        };
+        let mut datum = to_datum(json!({ "city": "a", "town": "b" }));
+        assert!(apply_step(&rename, &mut datum).is_err());
+        assert_eq!(datum, to_datum(json!({ "city": "a", "town": "b" })));
+
+        let mut datum = to_datum(json!({ "city": "a", "town": null }));
+        assert!(apply_step(&rename, &mut datum).is_ok());
+        assert_eq!(datum, to_datum(json!({ "town": "a" })));
+    }
+}
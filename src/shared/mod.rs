@@ -1,8 +1,21 @@
 pub mod backups;
+pub mod columns;
 pub mod config;
+pub mod data;
 pub mod datum;
+pub mod diff;
 pub mod errors;
+pub mod evolution;
+pub mod infer;
+pub mod issues;
+pub mod journal;
 pub mod json;
+pub mod keys;
+pub mod merge;
+pub mod migration;
 pub mod path;
 pub mod schema;
 pub mod scripts;
+pub mod snapshots;
+pub mod sqlite;
+pub mod type_rs;
@@ -12,3 +12,19 @@ pub fn style_path<'a>(path: &'a Path, default: &'a str) -> StyledObject<&'a str>
     }
     console::style(default).blue()
 }
+
+// relative paths are from base_dir, and a leading ~ is the home directory
+pub fn resolve_path(base_dir: &Path, path_str: &str) -> Box<Path> {
+    let home_rest = path_str
+        .strip_prefix("~/")
+        .or_else(|| path_str.strip_prefix("~\\"))
+        .or(if path_str == "~" { Some("") } else { None });
+    if let Some(home_rest) = home_rest {
+        let home_opt = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+        if let Some(home) = home_opt {
+            return Box::from(Path::new(&home).join(home_rest).as_path());
+        }
+    }
+    // join keeps the right side as is when it is absolute
+    Box::from(base_dir.join(path_str).as_path())
+}
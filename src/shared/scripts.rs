@@ -1,12 +1,24 @@
-use std::{collections::HashSet, path::Path};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-use super::{datum::Datum, errors::RunningErrors, path::style_path};
+use super::{
+    datum::{Collections, Datum},
+    errors::RunningErrors,
+    issues::Issue,
+    path::style_path,
+};
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum ScriptFn {
     Derive,
     Plot,
     Sort,
     Migrate,
+    Validate,
+    ValidateAll,
 }
 
 impl ScriptFn {
@@ -16,83 +28,450 @@ impl ScriptFn {
             ScriptFn::Plot => "plot",
             ScriptFn::Sort => "sort",
             ScriptFn::Migrate => "migrate",
+            ScriptFn::Validate => "validate",
+            ScriptFn::ValidateAll => "validate_all",
+        }
+    }
+
+    // every API function takes a single argument, but keep this per function
+    // so a mismatch is reported against the spec the user is reading
+    fn param_count(&self) -> usize {
+        match self {
+            ScriptFn::Derive => 1,
+            ScriptFn::Plot => 1,
+            ScriptFn::Sort => 1,
+            ScriptFn::Migrate => 1,
+            ScriptFn::Validate => 1,
+            ScriptFn::ValidateAll => 1,
         }
     }
 
     fn iter() -> std::slice::Iter<'static, ScriptFn> {
-        static SCRIPT_FNS: [ScriptFn; 4] = [
+        static SCRIPT_FNS: [ScriptFn; 6] = [
             ScriptFn::Derive,
             ScriptFn::Plot,
             ScriptFn::Sort,
             ScriptFn::Migrate,
+            ScriptFn::Validate,
+            ScriptFn::ValidateAll,
         ];
         SCRIPT_FNS.iter()
     }
 }
 
+// what the script is doing, so print and debug output can say where it came from
+#[derive(Default)]
+struct CallContext {
+    script_fn: Option<ScriptFn>,
+    index: Option<usize>,
+}
+
+impl CallContext {
+    fn fmt_prefix(&self) -> String {
+        let fn_str = self
+            .script_fn
+            .map_or("script", |script_fn| script_fn.to_str());
+        match self.index {
+            Some(index) => format!("[{fn_str} #{index}]"),
+            None => format!("[{fn_str}]"),
+        }
+    }
+}
+
 // lets us reuse a scripting configuration for multiple function calls
 pub struct RhaiSpace<'a> {
     pub engine: rhai::Engine,
     pub ast: rhai::AST,
     pub scope: rhai::Scope<'a>,
+    context: Rc<RefCell<CallContext>>,
+    imports: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+// resolves imports relative to the scripts file, and refuses to leave the project
+struct ProjectModuleResolver {
+    file_resolver: rhai::module_resolvers::FileModuleResolver,
+    scripts_dir: PathBuf,
+    root: PathBuf,
+    imports: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+impl rhai::ModuleResolver for ProjectModuleResolver {
+    fn resolve(
+        &self,
+        engine: &rhai::Engine,
+        source: Option<&str>,
+        path: &str,
+        pos: rhai::Position,
+    ) -> Result<rhai::Shared<rhai::Module>, Box<rhai::EvalAltResult>> {
+        // nested imports are relative to the module importing them, like rhai's own resolver
+        let source_dir = source
+            .and_then(|source| Path::new(source).parent())
+            .unwrap_or(&self.scripts_dir);
+        let file_path = self.file_resolver.get_file_path(path, Some(source_dir));
+        let runtime_err = |message: String| -> Box<rhai::EvalAltResult> {
+            rhai::EvalAltResult::ErrorRuntime(message.into(), pos).into()
+        };
+        // a path that can't be canonicalized can't be checked, so it's never loaded
+        let canonical_path = file_path.canonicalize().map_err(|err| {
+            runtime_err(format!(
+                "Cannot import {}: {err}",
+                file_path.to_string_lossy()
+            ))
+        })?;
+        if !canonical_path.starts_with(&self.root) {
+            return Err(runtime_err(format!(
+                "Cannot import {}, since it is outside the project directory {}",
+                canonical_path.to_string_lossy(),
+                self.root.to_string_lossy()
+            )));
+        }
+        {
+            let mut imports = self.imports.borrow_mut();
+            if !imports.contains(&canonical_path) {
+                imports.push(canonical_path.clone());
+            }
+        }
+        // load exactly the path that was checked, which also becomes the module's source
+        self.file_resolver
+            .resolve(engine, None, &canonical_path.to_string_lossy(), pos)
+    }
+}
+
+// print and debug in scripts, shown with what the script is doing
+fn set_output_hooks(engine: &mut rhai::Engine, context: &Rc<RefCell<CallContext>>, show: bool) {
+    let print_context = context.clone();
+    engine.on_print(move |text| {
+        if show {
+            eprintln!(
+                "{} {}",
+                console::style(print_context.borrow().fmt_prefix()).italic(),
+                console::style(text).magenta()
+            );
+        }
+    });
+    let debug_context = context.clone();
+    engine.on_debug(move |text, _source, pos| {
+        if show {
+            eprintln!(
+                "{} {} {}",
+                console::style(debug_context.borrow().fmt_prefix()).italic(),
+                console::style(format!("debug at {pos}:")).dim(),
+                console::style(text).magenta()
+            );
+        }
+    });
 }
 
 impl RhaiSpace<'_> {
-    pub fn new(scripts_path: &Path) -> Self {
+    // like in the repl, where printing is how a script shows what it's doing
+    pub fn show_output(&mut self) {
+        set_output_hooks(&mut self.engine, &self.context, true);
+    }
+
+    pub fn new(
+        scripts_path: &Path,
+        project_root: &Path,
+        required_fns: &[ScriptFn],
+        debug: bool,
+    ) -> Self {
+        let mut running_errors = RunningErrors::new();
+        let rhai_space_opt = Self::try_new(
+            scripts_path,
+            project_root,
+            required_fns,
+            debug,
+            &mut running_errors,
+        );
+        running_errors.print_errs();
+        match rhai_space_opt {
+            Some(rhai_space) => rhai_space,
+            None => panic!(),
+        }
+    }
+
+    // collects problems instead of stopping at the first, for checking everything at once
+    pub fn try_new(
+        scripts_path: &Path,
+        project_root: &Path,
+        required_fns: &[ScriptFn],
+        debug: bool,
+        running_errors: &mut RunningErrors,
+    ) -> Option<Self> {
         // make engine with ability to manip datum
         let mut engine: rhai::Engine = rhai::Engine::new();
         engine.register_type::<Datum>();
 
-        // parse script
-        let script = std::fs::read_to_string(scripts_path)
-            .expect(format!("Should read {}", style_path(scripts_path, "scripts file")).as_str());
-        let ast = engine.compile(&script).expect(
-            format!(
-                "{} is a valid rhai script",
-                style_path(scripts_path, "scripts file")
-            )
-            .as_str(),
-        );
+        // script output is only wanted while debugging, and then with context
+        let context = Rc::new(RefCell::new(CallContext::default()));
+        set_output_hooks(&mut engine, &context, debug);
+
+        // let scripts import modules from the project
+        let imports = Rc::new(RefCell::new(Vec::new()));
+        let scripts_dir = scripts_path.parent().unwrap_or(Path::new("."));
+        let root = match project_root.canonicalize() {
+            Ok(root) => root,
+            Err(err) => {
+                running_errors.add_err(
+                    &err.to_string(),
+                    format!(
+                        "Could not find {}.",
+                        style_path(project_root, "project directory")
+                    ),
+                );
+                return None;
+            }
+        };
+        engine.set_module_resolver(ProjectModuleResolver {
+            file_resolver: rhai::module_resolvers::FileModuleResolver::new(),
+            scripts_dir: scripts_dir.to_path_buf(),
+            root,
+            imports: imports.clone(),
+        });
+
+        // parse script, resolving imports now so they're known before any fn is called
+        let script = match std::fs::read_to_string(scripts_path) {
+            Ok(script) => script,
+            Err(err) => {
+                running_errors.add_err(
+                    &err.to_string(),
+                    format!(
+                        "Could not read {}.",
+                        style_path(scripts_path, "scripts file")
+                    ),
+                );
+                return None;
+            }
+        };
+        let ast = match engine.compile_into_self_contained(&rhai::Scope::new(), &script) {
+            Ok(ast) => ast,
+            Err(err) => {
+                running_errors.add_err(
+                    &"Rhai script is invalid".to_string(),
+                    format!("{}: {}", style_path(scripts_path, "scripts file"), err),
+                );
+                return None;
+            }
+        };
 
         // ensure the script meets our API
-        let mut missing_fn_set = HashSet::<&str>::new();
+        let err_type = "Rhai script does not satisfy API".to_string();
+        let warn_type = "Rhai script may not satisfy API".to_string();
+        let mut api_errors = RunningErrors::new();
         for script_fn in ScriptFn::iter() {
-            missing_fn_set.insert(script_fn.to_str());
+            let script_fn_str = script_fn.to_str();
+            let param_counts: Vec<usize> = ast
+                .iter_functions()
+                .filter(|function| function.name.eq(script_fn_str))
+                .map(|function| function.params.len())
+                .collect();
+            if param_counts.is_empty() {
+                if required_fns.contains(script_fn) {
+                    api_errors.add_err(
+                        &err_type,
+                        format!(
+                            "Did not implement {} function",
+                            console::style(script_fn_str).magenta()
+                        ),
+                    );
+                }
+            } else if !param_counts.contains(&script_fn.param_count()) {
+                api_errors.add_err(
+                    &err_type,
+                    format!(
+                        "{} function takes {} parameter(s), but should take {}",
+                        console::style(script_fn_str).magenta(),
+                        param_counts[0],
+                        script_fn.param_count()
+                    ),
+                );
+            }
         }
         for function in ast.iter_functions() {
             // unlikely this iter is every going to be on a scale where performance matters
-            for script_fn in ScriptFn::iter() {
-                let script_fn_str = script_fn.to_str();
-                if function.name.eq(script_fn_str) {
-                    missing_fn_set.remove(script_fn_str);
-                }
+            let is_api_fn = ScriptFn::iter().any(|script_fn| function.name.eq(script_fn.to_str()));
+            if is_api_fn {
+                continue;
             }
-        }
-        if !missing_fn_set.is_empty() {
-            let err_type = "Rhai script does not satisfy API".to_string();
-            let mut running_errors = RunningErrors::new();
-            for missing_fn in missing_fn_set {
-                running_errors.add_err(
-                    &err_type,
+            let similar_fn = ScriptFn::iter()
+                .find(|script_fn| is_typo_of(&function.name.to_lowercase(), script_fn.to_str()));
+            if let Some(similar_fn) = similar_fn {
+                api_errors.add_warn(
+                    &warn_type,
                     format!(
-                        "Did not implement {} function",
-                        console::style(missing_fn).cyan()
+                        "Found {} function; did you mean {}?",
+                        console::style(function.name).magenta(),
+                        console::style(similar_fn.to_str()).magenta()
                     ),
-                )
+                );
             }
-            running_errors.print_errs();
+        }
+        let satisfies_api = !api_errors.has_errs();
+        running_errors.extend(api_errors);
+        if !satisfies_api {
+            return None;
         }
 
         // make shared scope
         let scope = rhai::Scope::new();
 
-        RhaiSpace { engine, ast, scope }
+        Some(RhaiSpace {
+            engine,
+            ast,
+            scope,
+            context,
+            imports,
+        })
+    }
+
+    // for optional fns, which are skipped when the script doesn't define them
+    pub fn has_fn(&self, name: ScriptFn) -> bool {
+        self.ast.iter_functions().any(|function| {
+            function.name.eq(name.to_str()) && function.params.len() == name.param_count()
+        })
+    }
+
+    // runs the optional validate fns, for rules JSON schema can't express
+    pub fn validate(&mut self, data: &[Datum]) -> Vec<Issue> {
+        let mut issues: Vec<Issue> = Vec::new();
+        if self.has_fn(ScriptFn::Validate) {
+            for (index, datum) in data.iter().enumerate() {
+                let fn_res = self.call_fn_at::<rhai::Dynamic>(
+                    ScriptFn::Validate,
+                    Some(index),
+                    (to_dynamic(datum),),
+                );
+                issues.extend(Self::to_issues(ScriptFn::Validate, fn_res, Some(index)));
+            }
+        }
+        if self.has_fn(ScriptFn::ValidateAll) {
+            let fn_res =
+                self.call_fn_at::<rhai::Dynamic>(ScriptFn::ValidateAll, None, (to_dynamic(&data),));
+            issues.extend(Self::to_issues(ScriptFn::ValidateAll, fn_res, None));
+        }
+        issues
+    }
+
+    // for workspace scripts, whose validate_all receives every dataset by name
+    pub fn validate_collections(&mut self, collections: &Collections) -> Vec<Issue> {
+        if !self.has_fn(ScriptFn::ValidateAll) {
+            return Vec::new();
+        }
+        let fn_res = self.call_fn_at::<rhai::Dynamic>(
+            ScriptFn::ValidateAll,
+            None,
+            (to_dynamic(collections),),
+        );
+        Self::to_issues(ScriptFn::ValidateAll, fn_res, None)
+    }
+
+    // a fn that fails is an issue too, so the rest of the data is still validated
+    fn to_issues(
+        name: ScriptFn,
+        fn_res: Result<rhai::Dynamic, Issue>,
+        index: Option<usize>,
+    ) -> Vec<Issue> {
+        let fn_value = match fn_res {
+            Ok(fn_value) => fn_value,
+            Err(issue) => return vec![issue],
+        };
+        match fn_value.try_cast::<rhai::Array>() {
+            Some(array) => array
+                .iter()
+                .map(|value| Issue::from_script(value, index))
+                .collect(),
+            None => vec![Issue::error(
+                index,
+                None,
+                format!(
+                    "{} fn should return an array",
+                    console::style(name.to_str()).magenta()
+                ),
+            )],
+        }
+    }
+
+    // modules imported by the scripts, which watch should also track
+    pub fn imported_paths(&self) -> Vec<PathBuf> {
+        self.imports.borrow().clone()
     }
 
     pub fn call_fn<T: Clone + 'static>(&mut self, name: ScriptFn, args: impl rhai::FuncArgs) -> T {
-        self
+        self.call_fn_at(name, None, args)
+            .unwrap_or_else(|issue| panic!("{}", issue.message))
+    }
+
+    // index is the record being handled, for fns called once per datum
+    // a runtime error is an issue at that record, so it's reported like the data's other problems
+    pub fn call_fn_at<T: Clone + 'static>(
+        &mut self,
+        name: ScriptFn,
+        index: Option<usize>,
+        args: impl rhai::FuncArgs,
+    ) -> Result<T, Issue> {
+        *self.context.borrow_mut() = CallContext {
+            script_fn: Some(name),
+            index,
+        };
+        let fn_res = self
             .engine
-            .call_fn::<T>(&mut self.scope, &self.ast, name.to_str(), args)
-            .expect(format!("Could run {} fn", console::style(name.to_str()).magenta()).as_str())
+            .call_fn::<T>(&mut self.scope, &self.ast, name.to_str(), args);
+        *self.context.borrow_mut() = CallContext::default();
+        fn_res.map_err(|err| {
+            Issue::error(
+                index,
+                None,
+                format!(
+                    "Could not run {} fn: {err}",
+                    console::style(name.to_str()).magenta()
+                ),
+            )
+        })
+    }
+}
+
+// short names are a few edits from many unrelated words, like sort from port or sum
+fn is_typo_of(name: &str, api_name: &str) -> bool {
+    let max_distance = (api_name.len() / 4).min(2);
+    edit_distance(name, api_name) <= max_distance
+}
+
+// levenshtein distance, used to catch typos of API function names
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut prev_row: Vec<usize> = (0..=b_chars.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = prev_row[j] + if a_char == *b_char { 0 } else { 1 };
+            row.push(substitution.min(prev_row[j + 1] + 1).min(row[j] + 1));
+        }
+        prev_row = row;
+    }
+    prev_row[b_chars.len()]
+}
+
+// rhai scripts see data as object maps and arrays rather than our rust types
+pub fn to_dynamic<T: serde::Serialize>(value: &T) -> rhai::Dynamic {
+    rhai::serde::to_dynamic(value).expect("Should convert data into a rhai value")
+}
+
+pub fn from_dynamic<T: for<'a> serde::Deserialize<'a>>(
+    value: &rhai::Dynamic,
+) -> Result<T, Box<rhai::EvalAltResult>> {
+    rhai::serde::from_dynamic::<T>(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typos_allow_fewer_edits_for_short_names() {
+        assert!(is_typo_of("sorts", "sort"));
+        assert!(!is_typo_of("sum", "sort"));
+        assert!(is_typo_of("migrat", "migrate"));
+        assert!(!is_typo_of("mitigate", "migrate"));
+        assert!(is_typo_of("valdiate", "validate"));
+        assert!(!is_typo_of("derive", "sort"));
     }
 }
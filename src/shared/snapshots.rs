@@ -0,0 +1,317 @@
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{
+    backups::get_free_backup_path,
+    config::Workspace,
+    data::{get_data_paths, is_data_path, is_split},
+    path::style_path,
+};
+
+// snapshots are manifests named like other backups, like snapshot_backup_TIMESTAMP.json
+const SNAPSHOT_NAME: &str = "snapshot.json";
+const SNAPSHOT_PREFIX: &str = "snapshot";
+// file contents shared by every snapshot, named by their hash so each is only stored once
+pub const BLOBS_DIRECTORY_NAME: &str = "blobs";
+const BLOB_EXTENSION: &str = "gz";
+
+/// What a snapshot holds, so it can be checked and restored as a whole
+#[allow(non_snake_case)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub hedaVersion: String,
+    pub createdAt: String,
+    pub files: Vec<ManifestFile>,
+}
+
+#[allow(non_snake_case)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ManifestFile {
+    // relative to the root directory, or absolute if outside it
+    pub path: String,
+    // of the uncompressed contents, naming its blob
+    pub sha256: String,
+}
+
+pub struct Snapshot {
+    pub path: Box<Path>,
+    pub manifest: Manifest,
+}
+
+// every file a migration depends on, so restoring them together is consistent
+fn get_project_files(workspace: &Workspace) -> Vec<Box<Path>> {
+    let mut paths: Vec<Box<Path>> = vec![workspace.config_path.clone()];
+    paths.extend(workspace.scripts_rhai.clone());
+    for config in &workspace.datasets {
+        // split data is stored file by file, so each can be restored to where it was
+        let data_paths = get_data_paths(&config.data_json).unwrap_or_default();
+        paths.extend(data_paths.into_iter().map(PathBuf::into_boxed_path));
+        paths.extend([
+            config.schema_json.clone(),
+            config.type_rs.clone(),
+            config.scripts_rhai.clone(),
+        ]);
+    }
+    let mut project_files: Vec<Box<Path>> = Vec::new();
+    for path in paths {
+        // like scripts, which datasets can share
+        if path.is_file() && !project_files.contains(&path) {
+            project_files.push(path);
+        }
+    }
+    project_files
+}
+
+fn get_manifest_path_str(root_directory: &Path, path: &Path) -> String {
+    match path.strip_prefix(root_directory) {
+        Ok(relative_path) => relative_path.to_string_lossy().to_string(),
+        Err(_) => path.to_string_lossy().to_string(),
+    }
+}
+
+pub fn get_blobs_directory(backups_directory: &Path) -> PathBuf {
+    backups_directory.join(BLOBS_DIRECTORY_NAME)
+}
+
+fn get_blob_path(backups_directory: &Path, hash: &str) -> PathBuf {
+    get_blobs_directory(backups_directory)
+        .join(hash)
+        .with_extension(BLOB_EXTENSION)
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+// compresses the contents into a blob, unless an identical one is already stored
+fn write_blob(backups_directory: &Path, bytes: &[u8]) -> String {
+    let hash = hash_bytes(bytes);
+    let blob_path = get_blob_path(backups_directory, &hash);
+    if blob_path.is_file() {
+        return hash;
+    }
+    let blobs_directory = get_blobs_directory(backups_directory);
+    std::fs::create_dir_all(&blobs_directory)
+        .expect(format!("Should create {}", style_path(&blobs_directory, "blobs")).as_str());
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes).expect("compressed blob");
+    let compressed = encoder.finish().expect("compressed blob");
+    // written whole then renamed, so an interrupted backup can't leave a truncated blob
+    let partial_path = blob_path.with_extension("partial");
+    std::fs::write(&partial_path, compressed)
+        .expect(format!("Should write {}", style_path(&partial_path, "blob")).as_str());
+    std::fs::rename(&partial_path, &blob_path)
+        .expect(format!("Should write {}", style_path(&blob_path, "blob")).as_str());
+    hash
+}
+
+// the uncompressed contents, checked against the hash they're stored under
+pub fn read_blob(backups_directory: &Path, hash: &str) -> Result<Vec<u8>, String> {
+    let blob_path = get_blob_path(backups_directory, hash);
+    let compressed =
+        std::fs::read(&blob_path).map_err(|err| format!("{hash} is missing: {err}"))?;
+    let mut bytes: Vec<u8> = Vec::new();
+    flate2::read::GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut bytes)
+        .map_err(|err| format!("{hash} could not be decompressed: {err}"))?;
+    if hash_bytes(&bytes) != hash {
+        return Err(format!("{hash} does not match its contents"));
+    }
+    Ok(bytes)
+}
+
+// stores every project file, returning the manifest of the new snapshot
+pub fn make_snapshot(workspace: &Workspace) -> Box<Path> {
+    let backups_directory = &workspace.backups_directory;
+    let mut files: Vec<ManifestFile> = Vec::new();
+    for path in get_project_files(workspace) {
+        let bytes = std::fs::read(&path)
+            .expect(format!("Should read {}", style_path(&path, "file")).as_str());
+        files.push(ManifestFile {
+            path: get_manifest_path_str(&workspace.root_directory, &path),
+            sha256: write_blob(backups_directory, &bytes),
+        });
+    }
+    let manifest = Manifest {
+        hedaVersion: env!("CARGO_PKG_VERSION").to_string(),
+        createdAt: chrono::Local::now().to_rfc3339(),
+        files,
+    };
+    let manifest_str = serde_json::to_string_pretty(&manifest).expect("turned manifest to string");
+    let manifest_path = get_free_backup_path(Path::new(SNAPSHOT_NAME), backups_directory);
+    std::fs::write(&manifest_path, manifest_str)
+        .expect(format!("Should write {}", style_path(&manifest_path, "snapshot")).as_str());
+    manifest_path
+}
+
+// blobs no snapshot refers to, like after old snapshots are removed
+// none are unused while a snapshot can't be read, since it may be what uses them
+pub fn get_unused_blobs(backups_directory: &Path) -> Vec<PathBuf> {
+    let (snapshots, errs) = Snapshot::list(backups_directory);
+    if !errs.is_empty() {
+        return Vec::new();
+    }
+    let used_hashes: HashSet<String> = snapshots
+        .into_iter()
+        .flat_map(|snapshot| snapshot.manifest.files)
+        .map(|file| file.sha256)
+        .collect();
+    let Ok(entries) = std::fs::read_dir(get_blobs_directory(backups_directory)) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry_res| entry_res.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_stem()
+                .map_or(true, |stem| !used_hashes.contains(&*stem.to_string_lossy()))
+        })
+        .collect()
+}
+
+impl Snapshot {
+    // a truncated or corrupted manifest is an error, like a damaged blob
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let manifest_str = std::fs::read_to_string(path)
+            .map_err(|err| format!("Could not read {}: {err}", style_path(path, "snapshot")))?;
+        let manifest: Manifest = serde_json::from_str(&manifest_str).map_err(|err| {
+            format!(
+                "{} does not list the files of a snapshot: {err}",
+                style_path(path, "snapshot")
+            )
+        })?;
+        Ok(Snapshot {
+            path: Box::from(path),
+            manifest,
+        })
+    }
+
+    // oldest first, ignoring backups that aren't snapshots
+    // with the errors of snapshots that couldn't be read, which can't be ordered
+    pub fn list(backups_directory: &Path) -> (Vec<Self>, Vec<String>) {
+        let Ok(entries) = std::fs::read_dir(backups_directory) else {
+            return (Vec::new(), Vec::new());
+        };
+        let (mut snapshots, mut errs) = (Vec::new(), Vec::new());
+        let paths = entries
+            .filter_map(|entry_res| entry_res.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with(SNAPSHOT_PREFIX))
+            });
+        for path in paths {
+            match Snapshot::read(&path) {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(err) => errs.push(err),
+            }
+        }
+        snapshots.sort_by(|a: &Snapshot, b| a.manifest.createdAt.cmp(&b.manifest.createdAt));
+        errs.sort();
+        (snapshots, errs)
+    }
+
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    fn get_backups_directory(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new(""))
+    }
+
+    // the contents of a project file when the snapshot was made, if it has them
+    pub fn read_file(&self, root_directory: &Path, path: &Path) -> Option<Vec<u8>> {
+        let path_str = get_manifest_path_str(root_directory, path);
+        let file = self
+            .manifest
+            .files
+            .iter()
+            .find(|file| file.path == path_str)?;
+        read_blob(self.get_backups_directory(), &file.sha256).ok()
+    }
+
+    // every file of the data, which is several when it's split, or None if any can't be read
+    pub fn read_data_files(
+        &self,
+        root_directory: &Path,
+        data_path: &Path,
+    ) -> Option<Vec<(PathBuf, Vec<u8>)>> {
+        if !is_split(data_path) {
+            let bytes = self.read_file(root_directory, data_path)?;
+            return Some(vec![(data_path.to_path_buf(), bytes)]);
+        }
+        self.manifest
+            .files
+            .iter()
+            .map(|file| (root_directory.join(&file.path), file))
+            .filter(|(path, _)| is_data_path(data_path, path))
+            .map(|(path, file)| {
+                let bytes = read_blob(self.get_backups_directory(), &file.sha256).ok()?;
+                Some((path, bytes))
+            })
+            .collect()
+    }
+
+    // files of split data added since the snapshot, like records imported into their own files
+    pub fn remove_added_data_files(&self, root_directory: &Path, data_path: &Path) {
+        if !is_split(data_path) {
+            return;
+        }
+        let snapshot_paths: Vec<PathBuf> = self
+            .manifest
+            .files
+            .iter()
+            .map(|file| root_directory.join(&file.path))
+            .collect();
+        for path in get_data_paths(data_path).unwrap_or_default() {
+            if !snapshot_paths.contains(&path) {
+                std::fs::remove_file(&path)
+                    .expect(format!("Should remove {}", style_path(&path, "data")).as_str());
+            }
+        }
+    }
+
+    // files whose blob is missing or corrupted, with what's wrong with it
+    pub fn get_damaged_files(&self) -> Vec<(String, String)> {
+        self.manifest
+            .files
+            .iter()
+            .filter_map(|file| {
+                read_blob(self.get_backups_directory(), &file.sha256)
+                    .err()
+                    .map(|err| (file.path.clone(), err))
+            })
+            .collect()
+    }
+
+    // every file is written back, so the project is as it was when the snapshot was made
+    pub fn restore(&self, root_directory: &Path) {
+        for file in &self.manifest.files {
+            // join keeps the right side as is when it is absolute
+            let original_path = root_directory.join(&file.path);
+            if let Some(parent) = original_path.parent() {
+                std::fs::create_dir_all(parent).expect(
+                    format!(
+                        "Should create directory of {}",
+                        style_path(&original_path, "file")
+                    )
+                    .as_str(),
+                );
+            }
+            let bytes = read_blob(self.get_backups_directory(), &file.sha256)
+                .unwrap_or_else(|err| panic!("Should read blob of {}: {err}", file.path));
+            std::fs::write(&original_path, bytes)
+                .expect(format!("Should restore {}", style_path(&original_path, "file")).as_str());
+        }
+    }
+}
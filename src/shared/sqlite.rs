@@ -0,0 +1,510 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rusqlite::types::Value as SqlValue;
+use serde_json::Value;
+
+use super::{
+    columns::{get_struct_columns, Column},
+    datum::get_field,
+    type_rs::{TypeDef, TypeExpr, TypeFile},
+};
+
+// ids count rows in the order they're written, so the same data always gets the same ids
+const ID_COLUMN: &str = "_id";
+const PARENT_COLUMN: &str = "_parent_id";
+// index within the array the row came from, since tables have no order of their own
+const POSITION_COLUMN: &str = "_position";
+// items of arrays that aren't structs, like tags in ["a", "b"]
+const VALUE_COLUMN: &str = "value";
+
+// a table for records of a struct, with arrays in them normalized into child tables
+pub struct Table {
+    pub name: String,
+    columns: Vec<Column>,
+    // field of each record holding the items of the child table
+    children: Vec<(String, Table)>,
+    is_value: bool,
+    unique_path: Option<String>,
+    // field -> table and field of the key it references
+    references: BTreeMap<String, (String, String)>,
+}
+
+// dotted paths of nested fields aren't valid SQL names without quoting
+fn to_sql_name(path: &str) -> String {
+    path.replace('.', "_")
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn unwrap_option(ty: &TypeExpr) -> &TypeExpr {
+    match ty {
+        TypeExpr::Option(inner) => unwrap_option(inner),
+        ty => ty,
+    }
+}
+
+// anything that isn't a number is stored as text, with structured values as JSON
+fn to_sql_type(ty: &TypeExpr) -> Option<&'static str> {
+    match unwrap_option(ty) {
+        TypeExpr::Primitive(name) if name == "String" || name == "char" => Some("TEXT"),
+        TypeExpr::Primitive(name) if name == "f32" || name == "f64" => Some("REAL"),
+        TypeExpr::Primitive(_) => Some("INTEGER"),
+        // without a declared type, numbers and strings in it keep their own types
+        TypeExpr::Value => None,
+        _ => Some("TEXT"),
+    }
+}
+
+fn to_sql_value(value_opt: Option<&Value>) -> SqlValue {
+    match value_opt {
+        None | Some(Value::Null) => SqlValue::Null,
+        Some(Value::Bool(bool_value)) => SqlValue::Integer(i64::from(*bool_value)),
+        Some(Value::Number(number)) => match number.as_i64() {
+            Some(int) => SqlValue::Integer(int),
+            None => SqlValue::Real(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        Some(Value::String(str)) => SqlValue::Text(str.clone()),
+        Some(value) => SqlValue::Text(value.to_string()),
+    }
+}
+
+impl Table {
+    pub fn new(type_file: &TypeFile, name: &str) -> Self {
+        Self::from_struct(type_file, name, &type_file.root, &[])
+    }
+
+    // structs in ancestors are kept as JSON, since they'd nest tables forever
+    fn from_struct(
+        type_file: &TypeFile,
+        name: &str,
+        struct_name: &str,
+        ancestors: &[String],
+    ) -> Self {
+        let mut inner_ancestors = ancestors.to_vec();
+        inner_ancestors.push(struct_name.to_string());
+        let mut columns: Vec<Column> = Vec::new();
+        let mut children: Vec<(String, Table)> = Vec::new();
+        for column in get_struct_columns(type_file, struct_name, ancestors) {
+            match unwrap_option(&column.ty) {
+                TypeExpr::Vec(item_ty) | TypeExpr::Set(item_ty) => {
+                    let child_name = format!("{name}_{}", to_sql_name(&column.path));
+                    let child = Self::from_item(type_file, &child_name, item_ty, &inner_ancestors);
+                    children.push((column.path, child));
+                }
+                _ => columns.push(column),
+            }
+        }
+        Table {
+            name: name.to_string(),
+            columns,
+            children,
+            is_value: false,
+            unique_path: None,
+            references: BTreeMap::new(),
+        }
+    }
+
+    fn from_item(
+        type_file: &TypeFile,
+        name: &str,
+        item_ty: &TypeExpr,
+        ancestors: &[String],
+    ) -> Self {
+        if let TypeExpr::Named(struct_name) = unwrap_option(item_ty) {
+            let is_struct = type_file
+                .defs
+                .iter()
+                .any(|def| matches!(def, TypeDef::Struct(_)) && def.name() == struct_name);
+            if is_struct && !ancestors.contains(struct_name) {
+                return Self::from_struct(type_file, name, struct_name, ancestors);
+            }
+        }
+        Table {
+            name: name.to_string(),
+            columns: vec![Column {
+                path: VALUE_COLUMN.to_string(),
+                ty: item_ty.clone(),
+            }],
+            children: Vec::new(),
+            is_value: true,
+            unique_path: None,
+            references: BTreeMap::new(),
+        }
+    }
+
+    // keys only apply to fields with a column of their own, not ones in child tables
+    pub fn with_keys(
+        mut self,
+        primary_key: Option<&String>,
+        references: BTreeMap<String, (String, String)>,
+    ) -> Self {
+        let has_column = |path: &String| self.columns.iter().any(|column| &column.path == path);
+        self.unique_path = primary_key.filter(|path| has_column(path)).cloned();
+        self.references = references
+            .into_iter()
+            .filter(|(path, _)| has_column(path))
+            .collect();
+        self
+    }
+
+    // dotted paths flatten into names a field can already have, like address_city for address.city
+    pub fn get_name_clashes(&self) -> Vec<String> {
+        let mut clashes: Vec<String> = Vec::new();
+        let mut column_paths: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+        for column in &self.columns {
+            let sql_name = to_sql_name(&column.path);
+            if [ID_COLUMN, PARENT_COLUMN, POSITION_COLUMN].contains(&sql_name.as_str()) {
+                clashes.push(format!(
+                    "Field {} of table {} is named {sql_name} in SQL, which is kept for row ids",
+                    column.path, self.name
+                ));
+            }
+            column_paths.entry(sql_name).or_default().push(&column.path);
+        }
+        let mut child_paths: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (path, child) in &self.children {
+            child_paths.entry(&child.name).or_default().push(path);
+        }
+        for (sql_name, paths) in column_paths.iter().filter(|(_, paths)| paths.len() > 1) {
+            clashes.push(format!(
+                "Fields {} of table {} are all named {sql_name} in SQL",
+                paths.join(", "),
+                self.name
+            ));
+        }
+        for (child_name, paths) in child_paths.iter().filter(|(_, paths)| paths.len() > 1) {
+            clashes.push(format!(
+                "Fields {} of table {} all have items in table {child_name}",
+                paths.join(", "),
+                self.name
+            ));
+        }
+        for (_, child) in &self.children {
+            clashes.extend(child.get_name_clashes());
+        }
+        clashes
+    }
+
+    // returns how many tables were created, counting child tables
+    pub fn create(
+        &self,
+        tx: &rusqlite::Transaction,
+        parent: Option<&str>,
+    ) -> rusqlite::Result<usize> {
+        let mut column_defs: Vec<String> =
+            vec![format!("{} INTEGER PRIMARY KEY", quote(ID_COLUMN))];
+        if let Some(parent) = parent {
+            column_defs.push(format!(
+                "{} INTEGER NOT NULL REFERENCES {}({})",
+                quote(PARENT_COLUMN),
+                quote(parent),
+                quote(ID_COLUMN)
+            ));
+            column_defs.push(format!("{} INTEGER NOT NULL", quote(POSITION_COLUMN)));
+        }
+        for column in &self.columns {
+            let mut column_def = quote(&to_sql_name(&column.path));
+            if let Some(sql_type) = to_sql_type(&column.ty) {
+                column_def.push_str(&format!(" {sql_type}"));
+            }
+            if self.unique_path.as_ref() == Some(&column.path) {
+                column_def.push_str(" UNIQUE");
+            }
+            if let Some((table, path)) = self.references.get(&column.path) {
+                column_def.push_str(&format!(
+                    " REFERENCES {}({})",
+                    quote(table),
+                    quote(&to_sql_name(path))
+                ));
+            }
+            column_defs.push(column_def);
+        }
+        tx.execute(
+            &format!(
+                "CREATE TABLE {} ({})",
+                quote(&self.name),
+                column_defs.join(", ")
+            ),
+            [],
+        )?;
+        let mut table_count = 1;
+        for (_, child) in &self.children {
+            table_count += child.create(tx, Some(&self.name))?;
+        }
+        Ok(table_count)
+    }
+
+    // returns how many rows were inserted, counting rows of child tables
+    pub fn insert(&self, tx: &rusqlite::Transaction, items: &[Value]) -> rusqlite::Result<usize> {
+        self.insert_items(tx, None, items, &mut HashMap::new())
+    }
+
+    fn insert_items(
+        &self,
+        tx: &rusqlite::Transaction,
+        parent_id: Option<i64>,
+        items: &[Value],
+        last_ids: &mut HashMap<String, i64>,
+    ) -> rusqlite::Result<usize> {
+        let mut names: Vec<String> = vec![quote(ID_COLUMN)];
+        if parent_id.is_some() {
+            names.push(quote(PARENT_COLUMN));
+            names.push(quote(POSITION_COLUMN));
+        }
+        names.extend(
+            self.columns
+                .iter()
+                .map(|column| quote(&to_sql_name(&column.path))),
+        );
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(&self.name),
+            names.join(", "),
+            vec!["?"; names.len()].join(", ")
+        );
+
+        let mut row_count = 0;
+        for (position, item) in items.iter().enumerate() {
+            let last_id = last_ids.entry(self.name.clone()).or_insert(0);
+            *last_id += 1;
+            let id = *last_id;
+            let get_item_field = |path: &str| -> Option<&Value> {
+                if self.is_value {
+                    return Some(item);
+                }
+                get_field(item.as_object()?, path)
+            };
+
+            let mut values: Vec<SqlValue> = vec![SqlValue::Integer(id)];
+            if let Some(parent_id) = parent_id {
+                values.push(SqlValue::Integer(parent_id));
+                values.push(SqlValue::Integer(position as i64));
+            }
+            values.extend(
+                self.columns
+                    .iter()
+                    .map(|column| to_sql_value(get_item_field(&column.path))),
+            );
+            tx.prepare_cached(&sql)?
+                .execute(rusqlite::params_from_iter(values))?;
+            row_count += 1;
+
+            for (path, child) in &self.children {
+                let child_items = match get_item_field(path) {
+                    Some(Value::Array(child_items)) => child_items.as_slice(),
+                    _ => &[],
+                };
+                row_count += child.insert_items(tx, Some(id), child_items, last_ids)?;
+            }
+        }
+        Ok(row_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const PEOPLE_RS: &str = r#"
+pub struct Datum {
+    pub id: String,
+    pub address: Option<Address>,
+    pub tags: Vec<String>,
+    pub pets: Vec<Pet>,
+}
+
+pub struct Address {
+    pub city: String,
+}
+
+pub struct Pet {
+    pub name: String,
+    pub toys: Vec<String>,
+}
+"#;
+
+    const VISITS_RS: &str = r#"
+pub struct Datum {
+    pub person: String,
+}
+"#;
+
+    fn export(connection: &mut rusqlite::Connection) -> rusqlite::Result<(usize, usize)> {
+        let people = Table::new(&TypeFile::parse(PEOPLE_RS).unwrap(), "people")
+            .with_keys(Some(&"id".to_string()), BTreeMap::new());
+        let visits = Table::new(&TypeFile::parse(VISITS_RS).unwrap(), "visits").with_keys(
+            None,
+            BTreeMap::from([(
+                "person".to_string(),
+                ("people".to_string(), "id".to_string()),
+            )]),
+        );
+        let people_items = vec![
+            json!({
+                "id": "ann",
+                "address": { "city": "Oslo" },
+                "tags": ["a", "b"],
+                "pets": [{ "name": "rex", "toys": ["ball"] }, { "name": "tom", "toys": [] }],
+            }),
+            json!({ "id": "bob", "tags": [], "pets": [{ "name": "kit", "toys": ["yarn", "box"] }] }),
+        ];
+        let visit_items = vec![json!({ "person": "bob" })];
+        let tx = connection.transaction()?;
+        let table_count = people.create(&tx, None)? + visits.create(&tx, None)?;
+        let row_count = people.insert(&tx, &people_items)? + visits.insert(&tx, &visit_items)?;
+        tx.commit()?;
+        Ok((table_count, row_count))
+    }
+
+    fn get_rows(connection: &rusqlite::Connection, table: &str) -> Vec<Vec<SqlValue>> {
+        let mut statement = connection
+            .prepare(&format!("SELECT * FROM {} ORDER BY _id", quote(table)))
+            .unwrap();
+        let column_count = statement.column_count();
+        statement
+            .query_map([], |row| {
+                (0..column_count).map(|index| row.get(index)).collect()
+            })
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    fn text(str: &str) -> SqlValue {
+        SqlValue::Text(str.to_string())
+    }
+
+    #[test]
+    fn arrays_go_into_child_tables_of_their_records() {
+        let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+        assert_eq!(export(&mut connection).unwrap(), (5, 11));
+        assert_eq!(
+            get_rows(&connection, "people"),
+            vec![
+                vec![SqlValue::Integer(1), text("ann"), text("Oslo")],
+                vec![SqlValue::Integer(2), text("bob"), SqlValue::Null],
+            ]
+        );
+        // _id, _parent_id, _position, then the item
+        assert_eq!(
+            get_rows(&connection, "people_tags"),
+            vec![
+                vec![
+                    SqlValue::Integer(1),
+                    SqlValue::Integer(1),
+                    SqlValue::Integer(0),
+                    text("a")
+                ],
+                vec![
+                    SqlValue::Integer(2),
+                    SqlValue::Integer(1),
+                    SqlValue::Integer(1),
+                    text("b")
+                ],
+            ]
+        );
+        assert_eq!(
+            get_rows(&connection, "people_pets_toys"),
+            vec![
+                vec![
+                    SqlValue::Integer(1),
+                    SqlValue::Integer(1),
+                    SqlValue::Integer(0),
+                    text("ball")
+                ],
+                vec![
+                    SqlValue::Integer(2),
+                    SqlValue::Integer(3),
+                    SqlValue::Integer(0),
+                    text("yarn")
+                ],
+                vec![
+                    SqlValue::Integer(3),
+                    SqlValue::Integer(3),
+                    SqlValue::Integer(1),
+                    text("box")
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn exporting_twice_gives_the_same_rows() {
+        let mut first = rusqlite::Connection::open_in_memory().unwrap();
+        let mut second = rusqlite::Connection::open_in_memory().unwrap();
+        export(&mut first).unwrap();
+        export(&mut second).unwrap();
+        for table in [
+            "people",
+            "people_tags",
+            "people_pets",
+            "people_pets_toys",
+            "visits",
+        ] {
+            assert_eq!(get_rows(&first, table), get_rows(&second, table), "{table}");
+        }
+    }
+
+    #[test]
+    fn keys_become_constraints() {
+        let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+        export(&mut connection).unwrap();
+        let duplicate_res = connection.execute("INSERT INTO people (id) VALUES ('ann')", []);
+        assert!(duplicate_res
+            .unwrap_err()
+            .to_string()
+            .contains("UNIQUE constraint failed"));
+
+        let get_foreign_keys = |table: &str| -> Vec<(String, String, String)> {
+            let mut statement = connection
+                .prepare(&format!("PRAGMA foreign_key_list({})", quote(table)))
+                .unwrap();
+            statement
+                .query_map([], |row| Ok((row.get(2)?, row.get(3)?, row.get(4)?)))
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+        assert_eq!(
+            get_foreign_keys("visits"),
+            vec![("people".to_string(), "person".to_string(), "id".to_string())]
+        );
+        assert_eq!(
+            get_foreign_keys("people_pets_toys"),
+            vec![(
+                "people_pets".to_string(),
+                PARENT_COLUMN.to_string(),
+                ID_COLUMN.to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn flattened_names_that_clash_are_reported() {
+        let type_rs = r#"
+pub struct Datum {
+    pub address: Address,
+    pub address_city: String,
+    pub _id: String,
+}
+
+pub struct Address {
+    pub city: String,
+}
+"#;
+        let table = Table::new(&TypeFile::parse(type_rs).unwrap(), "people");
+        assert_eq!(
+            table.get_name_clashes(),
+            vec![
+                "Field _id of table people is named _id in SQL, which is kept for row ids",
+                "Fields address.city, address_city of table people are all named address_city in SQL",
+            ]
+        );
+        let table = Table::new(&TypeFile::parse(PEOPLE_RS).unwrap(), "people");
+        assert!(table.get_name_clashes().is_empty());
+    }
+}
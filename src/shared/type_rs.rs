@@ -0,0 +1,1232 @@
+use std::path::Path;
+
+use serde_json::json;
+
+use super::path::style_path;
+
+const ROOT_STRUCT: &str = "Datum";
+const DEFINITIONS_PREFIX: &str = "#/definitions/";
+
+// the subset of rust types that map onto JSON schema the way schemars maps them
+#[derive(Clone, PartialEq)]
+pub enum TypeExpr {
+    // String, bool, or a number type such as i64 or f64
+    Primitive(String),
+    Date,
+    DateTime,
+    Value,
+    Option(Box<TypeExpr>),
+    Vec(Box<TypeExpr>),
+    Set(Box<TypeExpr>),
+    Map(Box<TypeExpr>),
+    Named(String),
+}
+
+pub struct FieldDef {
+    // key in the JSON object, which may differ from the rust ident via serde rename
+    pub name: String,
+    pub doc: Option<String>,
+    pub ty: TypeExpr,
+    pub has_default: bool,
+}
+
+pub struct StructDef {
+    pub name: String,
+    pub doc: Option<String>,
+    pub fields: Vec<FieldDef>,
+    pub deny_unknown_fields: bool,
+}
+
+// only unit variants, which serialize as strings
+pub struct EnumDef {
+    pub name: String,
+    pub doc: Option<String>,
+    pub variants: Vec<String>,
+}
+
+pub enum TypeDef {
+    Struct(StructDef),
+    Enum(EnumDef),
+}
+
+impl TypeDef {
+    pub fn name(&self) -> &str {
+        match self {
+            TypeDef::Struct(struct_def) => &struct_def.name,
+            TypeDef::Enum(enum_def) => &enum_def.name,
+        }
+    }
+}
+
+// type.rs, parsed so it can generate schema.json without being compiled
+pub struct TypeFile {
+    pub root: String,
+    pub defs: Vec<TypeDef>,
+}
+
+impl TypeFile {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("Could not read {}: {err}", style_path(path, "type file")))?;
+        Self::parse(&contents)
+            .map_err(|err| format!("Could not parse {}: {err}", style_path(path, "type file")))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let file = syn::parse_file(contents).map_err(|err| err.to_string())?;
+        let mut defs: Vec<TypeDef> = Vec::new();
+        for item in &file.items {
+            match item {
+                syn::Item::Struct(item_struct) => {
+                    defs.push(TypeDef::Struct(parse_struct(item_struct)?))
+                }
+                syn::Item::Enum(item_enum) => defs.push(TypeDef::Enum(parse_enum(item_enum)?)),
+                _ => {}
+            }
+        }
+
+        // the datum struct is the root, falling back to the first struct
+        let root = defs
+            .iter()
+            .filter_map(|def| match def {
+                TypeDef::Struct(struct_def) => Some(struct_def.name.clone()),
+                TypeDef::Enum(_) => None,
+            })
+            .reduce(|first, name| if name == ROOT_STRUCT { name } else { first })
+            .ok_or("no struct is defined for each item in the data".to_string())?;
+        Ok(TypeFile { root, defs })
+    }
+
+    // schema for an array of the root struct, as schemars::schema_for!(Vec<Datum>) would make
+    pub fn to_schema(&self) -> Result<serde_json::Value, String> {
+        let mut definitions = serde_json::Map::new();
+        for def in &self.defs {
+            let def_schema = match def {
+                TypeDef::Struct(struct_def) => self.struct_schema(struct_def)?,
+                TypeDef::Enum(enum_def) => enum_schema(enum_def),
+            };
+            definitions.insert(def.name().to_string(), def_schema);
+        }
+        Ok(json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": format!("Array_of_{}", self.root),
+            "type": "array",
+            "items": { "$ref": format!("{DEFINITIONS_PREFIX}{}", self.root) },
+            "definitions": definitions,
+        }))
+    }
+
+    // the reverse of to_schema, for schemas shaped like schemars makes them
+    pub fn from_schema(schema: &serde_json::Value) -> Result<Self, String> {
+        let mut builder = SchemaReader { defs: Vec::new() };
+        if let Some(definitions) = schema.get("definitions").and_then(|defs| defs.as_object()) {
+            // reserve every name first, so inline types don't take them
+            for name in definitions.keys() {
+                builder.defs.push(TypeDef::Enum(EnumDef {
+                    name: name.clone(),
+                    doc: None,
+                    variants: Vec::new(),
+                }));
+            }
+            for (index, (name, def_schema)) in definitions.iter().enumerate() {
+                builder.defs[index] = builder.read_def(name, def_schema)?;
+            }
+        }
+
+        if schema.get("type").and_then(|ty| ty.as_str()) != Some("array") {
+            return Err("schema should be for an array".to_string());
+        }
+        let items = schema
+            .get("items")
+            .ok_or("schema should have items".to_string())?;
+        let root = match items.get("$ref").and_then(|ref_value| ref_value.as_str()) {
+            Some(ref_str) => ref_name(ref_str)?,
+            None => {
+                let def = builder.read_def(ROOT_STRUCT, items)?;
+                builder.defs.insert(0, def);
+                ROOT_STRUCT.to_string()
+            }
+        };
+        match builder.defs.iter().find(|def| def.name() == root) {
+            Some(TypeDef::Struct(_)) => Ok(TypeFile {
+                root,
+                defs: builder.defs,
+            }),
+            _ => Err(format!("items should be an object, but {root} is not")),
+        }
+    }
+
+    // declarations for TypeScript, with Data as the type of the whole file
+    pub fn to_typescript(&self) -> String {
+        let mut lines: Vec<String> = vec![format!("export type Data = {}[];", self.root)];
+        let root_first = self
+            .defs
+            .iter()
+            .filter(|def| def.name() == self.root)
+            .chain(self.defs.iter().filter(|def| def.name() != self.root));
+        for def in root_first {
+            lines.push(String::new());
+            match def {
+                TypeDef::Struct(struct_def) => {
+                    push_doc_typescript(&mut lines, &struct_def.doc, "");
+                    lines.push(format!("export interface {} {{", struct_def.name));
+                    for field in &struct_def.fields {
+                        push_doc_typescript(&mut lines, &field.doc, "  ");
+                        let is_optional =
+                            field.has_default || matches!(field.ty, TypeExpr::Option(_));
+                        lines.push(format!(
+                            "  {}{}: {};",
+                            to_typescript_key(&field.name),
+                            if is_optional { "?" } else { "" },
+                            field.ty.to_typescript()
+                        ));
+                    }
+                    lines.push("}".to_string());
+                }
+                TypeDef::Enum(enum_def) => {
+                    push_doc_typescript(&mut lines, &enum_def.doc, "");
+                    let variants: Vec<String> = enum_def
+                        .variants
+                        .iter()
+                        .map(|variant| format!("{variant:?}"))
+                        .collect();
+                    let variants_str = if variants.is_empty() {
+                        "never".to_string()
+                    } else {
+                        variants.join(" | ")
+                    };
+                    lines.push(format!("export type {} = {variants_str};", enum_def.name));
+                }
+            }
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    // rust source which parses back into the same TypeFile
+    pub fn to_rust(&self) -> String {
+        let mut uses: Vec<&str> = vec!["use serde::{Deserialize, Serialize};"];
+        let all_types: Vec<&TypeExpr> = self
+            .defs
+            .iter()
+            .flat_map(|def| match def {
+                TypeDef::Struct(struct_def) => {
+                    struct_def.fields.iter().map(|field| &field.ty).collect()
+                }
+                TypeDef::Enum(_) => Vec::new(),
+            })
+            .flat_map(|ty| ty.walk())
+            .collect();
+        let has_type = |is_type: fn(&TypeExpr) -> bool| all_types.iter().any(|ty| is_type(ty));
+        match (
+            has_type(|ty| matches!(ty, TypeExpr::Date)),
+            has_type(|ty| matches!(ty, TypeExpr::DateTime)),
+        ) {
+            (true, true) => uses.push("use chrono::{DateTime, NaiveDate, Utc};"),
+            (true, false) => uses.push("use chrono::NaiveDate;"),
+            (false, true) => uses.push("use chrono::{DateTime, Utc};"),
+            (false, false) => {}
+        }
+        if has_type(|ty| matches!(ty, TypeExpr::Value)) {
+            uses.push("use serde_json::Value;");
+        }
+        match (
+            has_type(|ty| matches!(ty, TypeExpr::Map(_))),
+            has_type(|ty| matches!(ty, TypeExpr::Set(_))),
+        ) {
+            (true, true) => uses.push("use std::collections::{BTreeMap, BTreeSet};"),
+            (true, false) => uses.push("use std::collections::BTreeMap;"),
+            (false, true) => uses.push("use std::collections::BTreeSet;"),
+            (false, false) => {}
+        }
+
+        uses.sort();
+        let mut lines: Vec<String> = uses.iter().map(|use_line| use_line.to_string()).collect();
+        // the root goes first, since it's the struct people read type.rs for
+        let root_first = self
+            .defs
+            .iter()
+            .filter(|def| def.name() == self.root)
+            .chain(self.defs.iter().filter(|def| def.name() != self.root));
+        for def in root_first {
+            lines.push(String::new());
+            match def {
+                TypeDef::Struct(struct_def) => push_struct_rust(&mut lines, struct_def),
+                TypeDef::Enum(enum_def) => push_enum_rust(&mut lines, enum_def),
+            }
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    fn struct_schema(&self, struct_def: &StructDef) -> Result<serde_json::Value, String> {
+        let mut properties = serde_json::Map::new();
+        let mut required: Vec<&str> = Vec::new();
+        for field in &struct_def.fields {
+            let mut field_schema = self.type_schema(&field.ty)?;
+            if let Some(doc) = &field.doc {
+                field_schema = with_doc(field_schema, doc);
+            }
+            if field.has_default {
+                if let Some(default) = self.default_value(&field.ty, &mut Vec::new()) {
+                    field_schema = with_default(field_schema, default);
+                }
+            }
+            properties.insert(field.name.clone(), field_schema);
+            if !field.has_default && !matches!(field.ty, TypeExpr::Option(_)) {
+                required.push(&field.name);
+            }
+        }
+        required.sort();
+
+        let mut schema = serde_json::Map::new();
+        schema.insert("type".to_string(), json!("object"));
+        if !required.is_empty() {
+            schema.insert("required".to_string(), json!(required));
+        }
+        schema.insert("properties".to_string(), properties.into());
+        if struct_def.deny_unknown_fields {
+            schema.insert("additionalProperties".to_string(), json!(false));
+        }
+        let schema = serde_json::Value::Object(schema);
+        Ok(match &struct_def.doc {
+            Some(doc) => with_doc(schema, doc),
+            None => schema,
+        })
+    }
+
+    fn type_schema(&self, ty: &TypeExpr) -> Result<serde_json::Value, String> {
+        Ok(match ty {
+            TypeExpr::Primitive(name) => primitive_schema(name)?,
+            TypeExpr::Date => json!({ "type": "string", "format": "date" }),
+            TypeExpr::DateTime => json!({ "type": "string", "format": "date-time" }),
+            TypeExpr::Value => json!(true),
+            TypeExpr::Option(inner) => {
+                let mut inner_schema = self.type_schema(inner)?;
+                match inner_schema.get("type").cloned() {
+                    Some(serde_json::Value::String(type_str))
+                        if inner_schema.get("$ref").is_none() =>
+                    {
+                        inner_schema["type"] = json!([type_str, "null"]);
+                        inner_schema
+                    }
+                    _ => json!({ "anyOf": [inner_schema, { "type": "null" }] }),
+                }
+            }
+            TypeExpr::Vec(inner) => json!({ "type": "array", "items": self.type_schema(inner)? }),
+            TypeExpr::Set(inner) => json!({
+                "type": "array",
+                "items": self.type_schema(inner)?,
+                "uniqueItems": true,
+            }),
+            TypeExpr::Map(inner) => json!({
+                "type": "object",
+                "additionalProperties": self.type_schema(inner)?,
+            }),
+            TypeExpr::Named(name) => {
+                if !self.defs.iter().any(|def| def.name() == name) {
+                    return Err(format!("type {name} is not defined in the type file"));
+                }
+                json!({ "$ref": format!("{DEFINITIONS_PREFIX}{name}") })
+            }
+        })
+    }
+
+    // what Default::default() serializes to, which schemars includes for #[serde(default)]
+    fn default_value(
+        &self,
+        ty: &TypeExpr,
+        ancestors: &mut Vec<String>,
+    ) -> Option<serde_json::Value> {
+        Some(match ty {
+            TypeExpr::Primitive(name) => match name.as_str() {
+                "String" => json!(""),
+                "char" => json!("\u{0}"),
+                "bool" => json!(false),
+                "f32" | "f64" => json!(0.0),
+                _ => json!(0),
+            },
+            TypeExpr::Date => json!("1970-01-01"),
+            TypeExpr::DateTime => json!("1970-01-01T00:00:00Z"),
+            TypeExpr::Value | TypeExpr::Option(_) => serde_json::Value::Null,
+            TypeExpr::Vec(_) | TypeExpr::Set(_) => json!([]),
+            TypeExpr::Map(_) => json!({}),
+            // generated types derive Default, with enums defaulting to their first variant
+            TypeExpr::Named(name) => {
+                // a struct can only contain itself behind an Option or collection
+                if ancestors.contains(name) {
+                    return None;
+                }
+                match self.defs.iter().find(|def| def.name() == name)? {
+                    TypeDef::Enum(enum_def) => json!(enum_def.variants.first()?),
+                    TypeDef::Struct(struct_def) => {
+                        ancestors.push(name.clone());
+                        let mut object = serde_json::Map::new();
+                        for field in &struct_def.fields {
+                            let field_default = self.default_value(&field.ty, ancestors)?;
+                            object.insert(field.name.clone(), field_default);
+                        }
+                        ancestors.pop();
+                        serde_json::Value::Object(object)
+                    }
+                }
+            }
+        })
+    }
+}
+
+struct SchemaReader {
+    defs: Vec<TypeDef>,
+}
+
+impl SchemaReader {
+    fn read_def(&mut self, name: &str, schema: &serde_json::Value) -> Result<TypeDef, String> {
+        let doc = read_doc(schema);
+        if let Some(variants) = schema.get("enum").and_then(|variants| variants.as_array()) {
+            let variants = variants
+                .iter()
+                .map(|variant| {
+                    variant
+                        .as_str()
+                        .map(|variant| variant.to_string())
+                        .ok_or(format!("enum {name} should only have string values"))
+                })
+                .collect::<Result<Vec<String>, String>>()?;
+            return Ok(TypeDef::Enum(EnumDef {
+                name: name.to_string(),
+                doc,
+                variants,
+            }));
+        }
+
+        if schema.get("type").and_then(|ty| ty.as_str()) != Some("object") {
+            return Err(format!("{name} should be an object or string enum"));
+        }
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(|required| required.as_array())
+            .map(|required| required.iter().filter_map(|key| key.as_str()).collect())
+            .unwrap_or_default();
+        let mut fields: Vec<FieldDef> = Vec::new();
+        let properties = schema.get("properties").and_then(|props| props.as_object());
+        for (key, field_schema) in properties.into_iter().flatten() {
+            let mut ty = self.read_type(key, field_schema)?;
+            let is_required = required.contains(&key.as_str());
+            let is_option = matches!(ty, TypeExpr::Option(_));
+            // schemars writes the default of every #[serde(default)] field, so others are options
+            let has_default = !is_required && field_schema.get("default").is_some();
+            if !is_required && !has_default && !is_option {
+                ty = TypeExpr::Option(Box::new(ty));
+            }
+            fields.push(FieldDef {
+                name: key.clone(),
+                doc: read_doc(field_schema),
+                ty,
+                has_default,
+            });
+        }
+        Ok(TypeDef::Struct(StructDef {
+            name: name.to_string(),
+            doc,
+            fields,
+            deny_unknown_fields: schema.get("additionalProperties") == Some(&json!(false)),
+        }))
+    }
+
+    fn read_type(&mut self, key: &str, schema: &serde_json::Value) -> Result<TypeExpr, String> {
+        let object = match schema {
+            serde_json::Value::Bool(true) => return Ok(TypeExpr::Value),
+            serde_json::Value::Object(object) => object,
+            _ => return Err(format!("{key} should have a schema object")),
+        };
+        if let Some(ref_str) = object.get("$ref").and_then(|ref_value| ref_value.as_str()) {
+            return Ok(TypeExpr::Named(ref_name(ref_str)?));
+        }
+        // a documented $ref, or Option of a $ref
+        if let Some(all_of) = object.get("allOf").and_then(|all_of| all_of.as_array()) {
+            if let [inner] = all_of.as_slice() {
+                return self.read_type(key, inner);
+            }
+        }
+        if let Some(any_of) = object.get("anyOf").and_then(|any_of| any_of.as_array()) {
+            let null_schema = json!({ "type": "null" });
+            let inner = match any_of.as_slice() {
+                [inner, null] | [null, inner] if *null == null_schema => Some(inner),
+                _ => None,
+            };
+            if let Some(inner) = inner {
+                return Ok(TypeExpr::Option(Box::new(self.read_type(key, inner)?)));
+            }
+            return Ok(TypeExpr::Value);
+        }
+        if let Some(variants) = object.get("enum") {
+            let name = self.unique_name(key);
+            let def = self.read_def(&name, &json!({ "enum": variants }))?;
+            self.defs.push(def);
+            return Ok(TypeExpr::Named(name));
+        }
+
+        let (type_str, is_nullable) = match object.get("type") {
+            Some(serde_json::Value::String(type_str)) => (type_str.as_str(), false),
+            Some(serde_json::Value::Array(types)) => {
+                let non_null: Vec<&str> = types
+                    .iter()
+                    .filter_map(|ty| ty.as_str())
+                    .filter(|ty| *ty != "null")
+                    .collect();
+                match non_null.as_slice() {
+                    [type_str] => (*type_str, non_null.len() < types.len()),
+                    _ => return Ok(TypeExpr::Value),
+                }
+            }
+            _ => return Ok(TypeExpr::Value),
+        };
+        let format = object.get("format").and_then(|format| format.as_str());
+        let ty = match type_str {
+            "string" => match format {
+                Some("date") => TypeExpr::Date,
+                Some("date-time") => TypeExpr::DateTime,
+                _ if object.get("minLength") == Some(&json!(1))
+                    && object.get("maxLength") == Some(&json!(1)) =>
+                {
+                    TypeExpr::Primitive("char".to_string())
+                }
+                _ => TypeExpr::Primitive("String".to_string()),
+            },
+            "boolean" => TypeExpr::Primitive("bool".to_string()),
+            "number" => match format {
+                Some("float") => TypeExpr::Primitive("f32".to_string()),
+                _ => TypeExpr::Primitive("f64".to_string()),
+            },
+            "integer" => TypeExpr::Primitive(match format {
+                Some("int") => "isize".to_string(),
+                Some("uint") => "usize".to_string(),
+                Some(int) if int.starts_with("int") => format!("i{}", &int[3..]),
+                Some(uint) if uint.starts_with("uint") => format!("u{}", &uint[4..]),
+                _ => "i64".to_string(),
+            }),
+            "array" => {
+                let items = match object.get("items") {
+                    Some(items) => self.read_type(key, items)?,
+                    None => TypeExpr::Value,
+                };
+                if object.get("uniqueItems") == Some(&json!(true)) {
+                    TypeExpr::Set(Box::new(items))
+                } else {
+                    TypeExpr::Vec(Box::new(items))
+                }
+            }
+            "object" if object.contains_key("properties") => {
+                let name = self.unique_name(key);
+                let def = self.read_def(&name, schema)?;
+                self.defs.push(def);
+                TypeExpr::Named(name)
+            }
+            "object" => match object.get("additionalProperties") {
+                Some(values) if values != &json!(false) => {
+                    TypeExpr::Map(Box::new(self.read_type(key, values)?))
+                }
+                _ => TypeExpr::Map(Box::new(TypeExpr::Value)),
+            },
+            _ => TypeExpr::Value,
+        };
+        Ok(if is_nullable {
+            TypeExpr::Option(Box::new(ty))
+        } else {
+            ty
+        })
+    }
+
+    // inline types are named after their field, numbered if the name is taken
+    fn unique_name(&self, key: &str) -> String {
+        let base = to_type_ident(key);
+        let mut name = base.clone();
+        let mut count = 2;
+        while self.defs.iter().any(|def| def.name() == name) {
+            name = format!("{base}{count}");
+            count += 1;
+        }
+        name
+    }
+}
+
+fn ref_name(ref_str: &str) -> Result<String, String> {
+    ref_str
+        .strip_prefix(DEFINITIONS_PREFIX)
+        .map(|name| name.to_string())
+        .ok_or(format!(
+            "$ref {ref_str} should point into {DEFINITIONS_PREFIX}"
+        ))
+}
+
+// the reverse of with_doc
+fn read_doc(schema: &serde_json::Value) -> Option<String> {
+    let title = schema.get("title").and_then(|title| title.as_str());
+    let description = schema
+        .get("description")
+        .and_then(|description| description.as_str());
+    match (title, description) {
+        (Some(title), Some(description)) => Some(format!("# {title}\n\n{description}")),
+        (Some(title), None) => Some(format!("# {title}")),
+        (None, Some(description)) => Some(description.to_string()),
+        (None, None) => None,
+    }
+}
+
+fn push_doc_typescript(lines: &mut Vec<String>, doc: &Option<String>, indent: &str) {
+    let Some(doc) = doc else {
+        return;
+    };
+    lines.push(format!("{indent}/**"));
+    for line in doc.lines() {
+        if line.is_empty() {
+            lines.push(format!("{indent} *"));
+        } else {
+            lines.push(format!("{indent} * {}", line.replace("*/", "*\\/")));
+        }
+    }
+    lines.push(format!("{indent} */"));
+}
+
+// keys which aren't identifiers are quoted, like "first name"
+fn to_typescript_key(name: &str) -> String {
+    let is_ident = name
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == '$')
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '$');
+    if is_ident {
+        name.to_string()
+    } else {
+        format!("{name:?}")
+    }
+}
+
+impl TypeExpr {
+    fn to_typescript(&self) -> String {
+        match self {
+            TypeExpr::Primitive(name) => match name.as_str() {
+                "String" | "char" => "string".to_string(),
+                "bool" => "boolean".to_string(),
+                _ => "number".to_string(),
+            },
+            TypeExpr::Date | TypeExpr::DateTime => "string".to_string(),
+            TypeExpr::Value => "unknown".to_string(),
+            TypeExpr::Option(inner) => format!("{} | null", inner.to_typescript()),
+            TypeExpr::Vec(inner) | TypeExpr::Set(inner) => match inner.as_ref() {
+                TypeExpr::Option(_) => format!("({})[]", inner.to_typescript()),
+                _ => format!("{}[]", inner.to_typescript()),
+            },
+            TypeExpr::Map(inner) => format!("Record<string, {}>", inner.to_typescript()),
+            TypeExpr::Named(name) => name.clone(),
+        }
+    }
+
+    // this type and every type inside it
+    fn walk(&self) -> Vec<&TypeExpr> {
+        let mut types = vec![self];
+        match self {
+            TypeExpr::Option(inner)
+            | TypeExpr::Vec(inner)
+            | TypeExpr::Set(inner)
+            | TypeExpr::Map(inner) => types.extend(inner.walk()),
+            _ => {}
+        }
+        types
+    }
+
+    fn to_rust(&self) -> String {
+        match self {
+            TypeExpr::Primitive(name) | TypeExpr::Named(name) => name.clone(),
+            TypeExpr::Date => "NaiveDate".to_string(),
+            TypeExpr::DateTime => "DateTime<Utc>".to_string(),
+            TypeExpr::Value => "Value".to_string(),
+            TypeExpr::Option(inner) => format!("Option<{}>", inner.to_rust()),
+            TypeExpr::Vec(inner) => format!("Vec<{}>", inner.to_rust()),
+            TypeExpr::Set(inner) => format!("BTreeSet<{}>", inner.to_rust()),
+            TypeExpr::Map(inner) => format!("BTreeMap<String, {}>", inner.to_rust()),
+        }
+    }
+}
+
+fn push_doc_rust(lines: &mut Vec<String>, doc: &Option<String>, indent: &str) {
+    for line in doc.iter().flat_map(|doc| doc.lines()) {
+        if line.is_empty() {
+            lines.push(format!("{indent}///"));
+        } else {
+            lines.push(format!("{indent}/// {line}"));
+        }
+    }
+}
+
+fn push_struct_rust(lines: &mut Vec<String>, struct_def: &StructDef) {
+    push_doc_rust(lines, &struct_def.doc, "");
+    // for fields with #[serde(default)], which can be of any type
+    lines.push("#[derive(Default, Serialize, Deserialize, schemars::JsonSchema)]".to_string());
+    if struct_def.deny_unknown_fields {
+        lines.push("#[serde(deny_unknown_fields)]".to_string());
+    }
+    lines.push(format!("pub struct {} {{", struct_def.name));
+    let mut idents: Vec<String> = Vec::new();
+    for field in &struct_def.fields {
+        push_doc_rust(lines, &field.doc, "    ");
+        let mut ident = to_field_ident(&field.name);
+        // distinct keys like "firstName" and "first_name" can share an ident
+        if idents.contains(&ident) {
+            ident = format!("{ident}_{}", idents.len());
+        }
+        idents.push(ident.clone());
+        if ident != field.name {
+            lines.push(format!("    #[serde(rename = {:?})]", field.name));
+        }
+        if field.has_default {
+            lines.push("    #[serde(default)]".to_string());
+        }
+        lines.push(format!("    pub {ident}: {},", field.ty.to_rust()));
+    }
+    lines.push("}".to_string());
+}
+
+fn push_enum_rust(lines: &mut Vec<String>, enum_def: &EnumDef) {
+    push_doc_rust(lines, &enum_def.doc, "");
+    lines.push("#[derive(Default, Serialize, Deserialize, schemars::JsonSchema)]".to_string());
+    lines.push(format!("pub enum {} {{", enum_def.name));
+    let mut idents: Vec<String> = Vec::new();
+    for (index, variant) in enum_def.variants.iter().enumerate() {
+        // the first variant, which default_value expects
+        if index == 0 {
+            lines.push("    #[default]".to_string());
+        }
+        let mut ident = to_type_ident(variant);
+        // distinct values like "a b" and "a-b" can share an ident
+        if idents.contains(&ident) {
+            ident = format!("{ident}{}", idents.len());
+        }
+        if ident != *variant {
+            lines.push(format!("    #[serde(rename = {variant:?})]"));
+        }
+        lines.push(format!("    {ident},"));
+        idents.push(ident);
+    }
+    lines.push("}".to_string());
+}
+
+// splits keys like "firstName", "first_name" or "First Name" into lowercase words
+fn split_words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut prev_lower = false;
+    for ch in name.chars() {
+        if !ch.is_ascii_alphanumeric() {
+            words.push(String::new());
+            prev_lower = false;
+            continue;
+        }
+        if words.is_empty() || (ch.is_ascii_uppercase() && prev_lower) {
+            words.push(String::new());
+        }
+        // unwrap is safe, since a word is pushed before the first char
+        words.last_mut().unwrap().push(ch.to_ascii_lowercase());
+        prev_lower = ch.is_ascii_lowercase() || ch.is_ascii_digit();
+    }
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+// a snake_case rust ident for a JSON key, which needs a serde rename if they differ
+fn to_field_ident(name: &str) -> String {
+    let ident = split_words(name).join("_");
+    let ident = match ident.chars().next() {
+        None => "field".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("field_{ident}"),
+        Some(_) => ident,
+    };
+    if syn::parse_str::<syn::Ident>(&ident).is_err() {
+        format!("{ident}_")
+    } else {
+        ident
+    }
+}
+
+// a PascalCase rust ident, for type names and enum variants
+pub fn to_type_ident(name: &str) -> String {
+    let ident: String = split_words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    match ident.chars().next() {
+        None => "Empty".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("V{ident}"),
+        Some(_) if syn::parse_str::<syn::Ident>(&ident).is_err() => format!("{ident}_"),
+        Some(_) => ident,
+    }
+}
+
+fn primitive_schema(name: &str) -> Result<serde_json::Value, String> {
+    Ok(match name {
+        "String" => json!({ "type": "string" }),
+        "char" => json!({ "type": "string", "minLength": 1, "maxLength": 1 }),
+        "bool" => json!({ "type": "boolean" }),
+        "f32" => json!({ "type": "number", "format": "float" }),
+        "f64" => json!({ "type": "number", "format": "double" }),
+        "isize" => json!({ "type": "integer", "format": "int" }),
+        "usize" => json!({ "type": "integer", "format": "uint", "minimum": 0.0 }),
+        int if int.starts_with('i') => {
+            json!({ "type": "integer", "format": format!("int{}", &int[1..]) })
+        }
+        uint if uint.starts_with('u') => json!({
+            "type": "integer",
+            "format": format!("uint{}", &uint[1..]),
+            "minimum": 0.0,
+        }),
+        other => return Err(format!("{other} is not a supported type")),
+    })
+}
+
+fn enum_schema(enum_def: &EnumDef) -> serde_json::Value {
+    let schema = json!({ "type": "string", "enum": enum_def.variants });
+    match &enum_def.doc {
+        Some(doc) => with_doc(schema, doc),
+        None => schema,
+    }
+}
+
+// like with_doc, a $ref gets wrapped since it can't have siblings
+fn with_default(schema: serde_json::Value, default: serde_json::Value) -> serde_json::Value {
+    let mut schema = match schema {
+        serde_json::Value::Object(map) if !map.contains_key("$ref") => map,
+        other => {
+            let mut map = serde_json::Map::new();
+            map.insert("allOf".to_string(), json!([other]));
+            map
+        }
+    };
+    schema.insert("default".to_string(), default);
+    serde_json::Value::Object(schema)
+}
+
+// schemars takes a leading "# " line as the title, and the rest as the description
+fn with_doc(schema: serde_json::Value, doc: &str) -> serde_json::Value {
+    let (title, description) = match doc.strip_prefix("# ") {
+        Some(titled) => match titled.split_once('\n') {
+            Some((title, rest)) => (Some(title.trim()), rest),
+            None => (Some(titled.trim()), ""),
+        },
+        None => (None, doc),
+    };
+    let description = description
+        .trim()
+        .split("\n\n")
+        .map(|paragraph| paragraph.trim().replace('\n', " "))
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    // a $ref can't have siblings in draft 7, so it gets wrapped
+    let mut schema = match schema {
+        serde_json::Value::Object(map) if !map.contains_key("$ref") => map,
+        other => {
+            let mut map = serde_json::Map::new();
+            map.insert("allOf".to_string(), json!([other]));
+            map
+        }
+    };
+    if let Some(title) = title {
+        schema.insert("title".to_string(), json!(title));
+    }
+    if !description.is_empty() {
+        schema.insert("description".to_string(), json!(description));
+    }
+    serde_json::Value::Object(schema)
+}
+
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    default: bool,
+    skip: bool,
+    deny_unknown_fields: bool,
+}
+
+fn parse_serde_attrs(attrs: &[syn::Attribute]) -> Result<SerdeAttrs, String> {
+    let mut serde_attrs = SerdeAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            let key = meta.path.get_ident().map(|ident| ident.to_string());
+            match key.as_deref() {
+                Some("rename") => {
+                    serde_attrs.rename = Some(meta.value()?.parse::<syn::LitStr>()?.value())
+                }
+                Some("rename_all") => {
+                    serde_attrs.rename_all = Some(meta.value()?.parse::<syn::LitStr>()?.value())
+                }
+                Some("default") => {
+                    serde_attrs.default = true;
+                    skip_meta(&meta)?;
+                }
+                Some("skip") | Some("skip_deserializing") => serde_attrs.skip = true,
+                Some("deny_unknown_fields") => serde_attrs.deny_unknown_fields = true,
+                _ => skip_meta(&meta)?,
+            }
+            Ok(())
+        })
+        .map_err(|err| err.to_string())?;
+    }
+    Ok(serde_attrs)
+}
+
+// consumes serde attributes which don't affect the schema, like skip_serializing_if = "..."
+fn skip_meta(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|inner| skip_meta(&inner))?;
+    }
+    Ok(())
+}
+
+fn parse_doc(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(lit_str),
+                        ..
+                    }),
+                ..
+            }) => Some(lit_str.value()),
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').unwrap_or(&line).to_string())
+        .collect();
+    let doc = lines.join("\n").trim().to_string();
+    if doc.is_empty() {
+        None
+    } else {
+        Some(doc)
+    }
+}
+
+fn parse_struct(item_struct: &syn::ItemStruct) -> Result<StructDef, String> {
+    let name = item_struct.ident.to_string();
+    let serde_attrs = parse_serde_attrs(&item_struct.attrs)?;
+    let named_fields = match &item_struct.fields {
+        syn::Fields::Named(named_fields) => named_fields,
+        syn::Fields::Unit => {
+            return Ok(StructDef {
+                name,
+                doc: parse_doc(&item_struct.attrs),
+                fields: Vec::new(),
+                deny_unknown_fields: serde_attrs.deny_unknown_fields,
+            })
+        }
+        syn::Fields::Unnamed(_) => return Err(format!("struct {name} should have named fields")),
+    };
+
+    let mut fields: Vec<FieldDef> = Vec::new();
+    for field in &named_fields.named {
+        let field_attrs = parse_serde_attrs(&field.attrs)?;
+        if field_attrs.skip {
+            continue;
+        }
+        // unwrap is safe, since named fields have idents
+        let ident = field.ident.as_ref().unwrap().to_string();
+        let ident = ident.strip_prefix("r#").unwrap_or(&ident).to_string();
+        let name = match (&field_attrs.rename, &serde_attrs.rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rename_all)) => apply_rename_all(&ident, rename_all, true)?,
+            (None, None) => ident.clone(),
+        };
+        fields.push(FieldDef {
+            name,
+            doc: parse_doc(&field.attrs),
+            ty: parse_type(&field.ty)?,
+            has_default: field_attrs.default || serde_attrs.default,
+        });
+    }
+    Ok(StructDef {
+        name,
+        doc: parse_doc(&item_struct.attrs),
+        fields,
+        deny_unknown_fields: serde_attrs.deny_unknown_fields,
+    })
+}
+
+fn parse_enum(item_enum: &syn::ItemEnum) -> Result<EnumDef, String> {
+    let name = item_enum.ident.to_string();
+    let serde_attrs = parse_serde_attrs(&item_enum.attrs)?;
+    let mut variants: Vec<String> = Vec::new();
+    for variant in &item_enum.variants {
+        let ident = variant.ident.to_string();
+        if !matches!(variant.fields, syn::Fields::Unit) {
+            return Err(format!(
+                "enum {name} should only have unit variants, but {ident} has fields"
+            ));
+        }
+        let variant_attrs = parse_serde_attrs(&variant.attrs)?;
+        if variant_attrs.skip {
+            continue;
+        }
+        let variant_name = match (&variant_attrs.rename, &serde_attrs.rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rename_all)) => apply_rename_all(&ident, rename_all, false)?,
+            (None, None) => ident.clone(),
+        };
+        variants.push(variant_name);
+    }
+    Ok(EnumDef {
+        name,
+        doc: parse_doc(&item_enum.attrs),
+        variants,
+    })
+}
+
+fn parse_type(ty: &syn::Type) -> Result<TypeExpr, String> {
+    let type_path = match ty {
+        syn::Type::Path(type_path) => type_path,
+        syn::Type::Reference(type_ref) => return parse_type(&type_ref.elem),
+        _ => return Err("only named types are supported".to_string()),
+    };
+    // unwrap is safe, since a path has at least one segment
+    let segment = type_path.path.segments.last().unwrap();
+    let args: Vec<&syn::Type> = match &segment.arguments {
+        syn::PathArguments::AngleBracketed(angle_args) => angle_args
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::GenericArgument::Type(arg_ty) => Some(arg_ty),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let ident = segment.ident.to_string();
+    let arg = |position: usize| {
+        args.get(position)
+            .ok_or(format!("{ident} should have a type argument"))
+            .and_then(|arg_ty| parse_type(arg_ty))
+    };
+
+    Ok(match ident.as_str() {
+        "String" | "str" => TypeExpr::Primitive("String".to_string()),
+        "char" | "bool" | "f32" | "f64" | "i8" | "i16" | "i32" | "i64" | "i128" | "isize"
+        | "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => TypeExpr::Primitive(ident),
+        "NaiveDate" => TypeExpr::Date,
+        "DateTime" => TypeExpr::DateTime,
+        "Value" => TypeExpr::Value,
+        "Box" => arg(0)?,
+        "Option" => TypeExpr::Option(Box::new(arg(0)?)),
+        "Vec" | "VecDeque" => TypeExpr::Vec(Box::new(arg(0)?)),
+        "HashSet" | "BTreeSet" => TypeExpr::Set(Box::new(arg(0)?)),
+        "HashMap" | "BTreeMap" => {
+            if arg(0)? != TypeExpr::Primitive("String".to_string()) {
+                return Err(format!("{ident} keys should be String"));
+            }
+            TypeExpr::Map(Box::new(arg(1)?))
+        }
+        _ => TypeExpr::Named(ident),
+    })
+}
+
+// serde's rename_all, for fields (snake_case idents) or variants (PascalCase idents)
+fn apply_rename_all(ident: &str, rule: &str, is_field: bool) -> Result<String, String> {
+    let words: Vec<String> = if is_field {
+        ident.split('_').map(|word| word.to_lowercase()).collect()
+    } else {
+        let mut words: Vec<String> = Vec::new();
+        for ch in ident.chars() {
+            if ch.is_uppercase() || words.is_empty() {
+                words.push(String::new());
+            }
+            // unwrap is safe, since a word is pushed before the first char
+            words.last_mut().unwrap().extend(ch.to_lowercase());
+        }
+        words
+    };
+    let capitalize = |word: &String| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+            None => String::new(),
+        }
+    };
+    Ok(match rule {
+        "lowercase" => words.concat(),
+        "UPPERCASE" => words.concat().to_uppercase(),
+        "PascalCase" => words.iter().map(capitalize).collect(),
+        "camelCase" => {
+            let pascal: String = words.iter().map(capitalize).collect();
+            let mut chars = pascal.chars();
+            match chars.next() {
+                Some(first) => first.to_lowercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        }
+        "snake_case" => words.join("_"),
+        "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+        "kebab-case" => words.join("-"),
+        "SCREAMING-KEBAB-CASE" => words.join("-").to_uppercase(),
+        other => return Err(format!("rename_all = \"{other}\" is not supported")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPE_RS: &str = r#"
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// # Person
+/// Someone tracked by the project.
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Datum {
+    /// Unique across every person
+    pub id: u32,
+    pub first_name: String,
+    pub initial: char,
+    pub nick_name: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    #[serde(default)]
+    pub scores: Vec<f64>,
+    pub ratio: f32,
+    pub born: NaiveDate,
+    pub updated: Option<DateTime<Utc>>,
+    /// Where they live
+    pub address: Address,
+    pub previous: Option<Address>,
+    #[serde(default)]
+    pub backup: Address,
+    pub status: Status,
+    #[serde(rename = "type")]
+    pub kind: i8,
+    pub extra: Value,
+    pub counts: HashMap<String, usize>,
+    pub notes: BTreeMap<String, Option<String>>,
+    #[serde(default)]
+    pub active: bool,
+}
+
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Address {
+    pub city: String,
+    pub zip: Option<i64>,
+}
+
+/// Whether they are still around
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Active,
+    OnLeave,
+    #[serde(rename = "gone")]
+    Left,
+}
+"#;
+
+    fn schema_of(type_rs: &str) -> serde_json::Value {
+        TypeFile::parse(type_rs).unwrap().to_schema().unwrap()
+    }
+
+    // what generate-types writes for TYPE_RS, compiled so schemars can say what its schema is
+    const GENERATED_RS: &str = include_str!("../../tests/fixtures/generated_type.rs");
+    #[allow(dead_code)]
+    mod generated {
+        include!("../../tests/fixtures/generated_type.rs");
+    }
+
+    #[test]
+    fn generated_rust_compiles_to_the_same_schema_with_schemars() {
+        let schema = schema_of(TYPE_RS);
+        assert_eq!(
+            TypeFile::from_schema(&schema).unwrap().to_rust(),
+            GENERATED_RS
+        );
+        let compiled_schema =
+            serde_json::to_value(schemars::schema_for!(Vec<generated::Datum>)).unwrap();
+        assert_eq!(compiled_schema, schema);
+    }
+
+    #[test]
+    fn from_schema_round_trips_to_the_same_schema() {
+        let schema = schema_of(TYPE_RS);
+        let type_file = TypeFile::from_schema(&schema).unwrap();
+        assert_eq!(type_file.to_schema().unwrap(), schema);
+    }
+
+    #[test]
+    fn generated_rust_round_trips_to_the_same_schema() {
+        let schema = schema_of(TYPE_RS);
+        let generated_rs = TypeFile::from_schema(&schema).unwrap().to_rust();
+        assert_eq!(schema_of(&generated_rs), schema);
+        // generating again from the generated code is stable
+        let regenerated_rs = TypeFile::from_schema(&schema_of(&generated_rs))
+            .unwrap()
+            .to_rust();
+        assert_eq!(regenerated_rs, generated_rs);
+    }
+
+    #[test]
+    fn generated_rust_renames_keys_which_are_not_idents() {
+        let schema = serde_json::json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["first name", "type", "2nd"],
+                "properties": {
+                    "first name": { "type": "string" },
+                    "type": { "type": "string" },
+                    "2nd": { "type": "boolean" },
+                },
+            },
+        });
+        let generated_rs = TypeFile::from_schema(&schema).unwrap().to_rust();
+        assert!(
+            generated_rs.contains("#[serde(rename = \"first name\")]\n    pub first_name: String,")
+        );
+        assert!(generated_rs.contains("#[serde(rename = \"type\")]\n    pub type_: String,"));
+        assert!(generated_rs.contains("#[serde(rename = \"2nd\")]\n    pub field_2nd: bool,"));
+        let reparsed = TypeFile::parse(&generated_rs).unwrap();
+        assert_eq!(
+            reparsed.to_schema().unwrap(),
+            TypeFile::from_schema(&schema).unwrap().to_schema().unwrap()
+        );
+    }
+
+    #[test]
+    fn typescript_marks_optional_and_nullable_fields() {
+        let typescript = TypeFile::from_schema(&schema_of(TYPE_RS))
+            .unwrap()
+            .to_typescript();
+        assert!(typescript.starts_with("export type Data = Datum[];\n"));
+        assert!(typescript.contains("export interface Datum {"));
+        assert!(typescript.contains("  firstName: string;"));
+        assert!(typescript.contains("  nickName?: string | null;"));
+        assert!(typescript.contains("  tags?: string[];"));
+        assert!(typescript.contains("  previous?: Address | null;"));
+        assert!(typescript.contains("  backup?: Address;"));
+        assert!(typescript.contains("  type: number;"));
+        assert!(typescript.contains("  extra: unknown;"));
+        assert!(typescript.contains("  notes: Record<string, string | null>;"));
+        assert!(typescript.contains("export type Status = \"active\" | \"on_leave\" | \"gone\";"));
+        assert!(
+            typescript.contains("/**\n * # Person\n *\n * Someone tracked by the project.\n */")
+        );
+    }
+}
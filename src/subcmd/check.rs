@@ -0,0 +1,272 @@
+use std::path::Path;
+
+use crate::shared::{
+    config::{get_config_issues, Config, Workspace},
+    data::{try_read_data_files, DataFile},
+    datum::{Collections, Datum},
+    errors::RunningErrors,
+    evolution::{compare_schemas, SchemaChange},
+    issues::{add_issues, Issue},
+    json::{try_get_schema_issues, try_read_json},
+    keys::get_key_issues,
+    merge::{get_conflict_issues, without_conflicted},
+    path::style_path,
+    scripts::RhaiSpace,
+    type_rs::TypeFile,
+};
+
+pub fn run_check(config_path: &Path, dataset_arg: Option<&String>, debug: bool) {
+    let mut running_errors = RunningErrors::new();
+
+    // the other files can't be found without a valid config
+    match get_config_issues(config_path) {
+        Ok(config_issues) => add_issues(&mut running_errors, "Config", &config_issues),
+        Err(err) => running_errors.add_err(&"Config error".to_string(), err),
+    }
+    if running_errors.has_errs() {
+        exit_with_errs(running_errors);
+    }
+    let workspace = Workspace::new(config_path);
+
+    let mut loaded: Vec<(&Config, Vec<Datum>, Vec<DataFile>)> = Vec::new();
+    for config in workspace.select(dataset_arg) {
+        if let Some((data, data_files)) = check_dataset(config, debug, &mut running_errors) {
+            loaded.push((config, data, data_files));
+        }
+    }
+
+    // datasets with unreadable data are left out, so their references aren't reported
+    let key_issues = get_key_issues(
+        &loaded
+            .iter()
+            .map(|(config, data, _)| (*config, data.as_slice()))
+            .collect::<Vec<(&Config, &[Datum])>>(),
+    );
+    let key_issues: Vec<Issue> = key_issues
+        .into_iter()
+        .map(|issue| {
+            let data_files = loaded
+                .iter()
+                .find(|(config, _, _)| config.name == issue.dataset)
+                .map(|(_, _, data_files)| data_files.as_slice())
+                .unwrap_or_default();
+            issue.in_data_file(data_files)
+        })
+        .collect();
+    add_issues(&mut running_errors, "Key", &key_issues);
+
+    let collections: Collections = loaded
+        .into_iter()
+        .filter_map(|(config, data, _)| config.name.clone().map(|name| (name, data)))
+        .collect();
+
+    // scripts across datasets need all of them, so are skipped when one is picked
+    if let (Some(scripts_rhai), None) = (&workspace.scripts_rhai, dataset_arg) {
+        let rhai_space_opt = RhaiSpace::try_new(
+            scripts_rhai,
+            &workspace.root_directory,
+            &[],
+            debug,
+            &mut running_errors,
+        );
+        let has_all_data = collections.len() == workspace.datasets.len();
+        if let (Some(mut rhai_space), true) = (rhai_space_opt, has_all_data) {
+            add_issues(
+                &mut running_errors,
+                "Validation",
+                &rhai_space.validate_collections(&collections),
+            );
+        }
+    }
+
+    if running_errors.has_errs() {
+        exit_with_errs(running_errors);
+    }
+    running_errors.print_errs();
+    println!("{} No errors found", console::style("Success:").green());
+}
+
+// returns the data when it has the right shape for scripts, even if failing the schema
+// with the files it was read from, when split across them
+fn check_dataset(
+    config: &Config,
+    debug: bool,
+    running_errors: &mut RunningErrors,
+) -> Option<(Vec<Datum>, Vec<DataFile>)> {
+    let (data_json_res, data_files) = match try_read_data_files(&config.data_json) {
+        Ok((data_json, data_files)) => (Ok(data_json), data_files),
+        Err(err) => (Err(err), Vec::new()),
+    };
+    let tag_issues = |issues: Vec<Issue>| -> Vec<Issue> {
+        issues
+            .into_iter()
+            .map(|issue| issue.in_dataset(&config.name).in_data_file(&data_files))
+            .collect()
+    };
+
+    // schema.json should have the types type.rs generates
+    let schema_json_res = try_read_json(&config.schema_json);
+    let type_rs_res = std::fs::read_to_string(&config.type_rs).map_err(|err| {
+        format!(
+            "Could not read {}: {err}",
+            style_path(&config.type_rs, "type file")
+        )
+    });
+    match (schema_json_res.as_ref(), type_rs_res.as_ref()) {
+        (Ok(schema_json), Ok(type_rs)) => match get_outdated_changes(schema_json, type_rs) {
+            Ok(None) => {}
+            Ok(Some(changes)) => {
+                running_errors.add_err(
+                    &"Schema error".to_string(),
+                    format!(
+                        "{} is out of date with {}; run heda migrate",
+                        style_path(&config.schema_json, "schema"),
+                        style_path(&config.type_rs, "type file")
+                    ),
+                );
+                for change in changes {
+                    running_errors.add_warn(&"Schema change".to_string(), change.fmt());
+                }
+            }
+            Err(err) => running_errors.add_err(
+                &"Schema error".to_string(),
+                format!(
+                    "Could not parse {}: {err}",
+                    style_path(&config.type_rs, "type file")
+                ),
+            ),
+        },
+        (Err(err), _) | (_, Err(err)) => {
+            running_errors.add_err(&"Schema error".to_string(), err.to_string())
+        }
+    }
+
+    // the data is checked against the schema.json it will be validated with elsewhere
+    match (&data_json_res, &schema_json_res) {
+        (Ok(data_json), Ok(schema_json)) => match try_get_schema_issues(data_json, schema_json) {
+            Ok(issues) => {
+                // conflicts left by heda merge-driver fail the schema too, which adds nothing
+                let conflict_issues = get_conflict_issues(data_json);
+                let issues = without_conflicted(issues, &conflict_issues);
+                add_issues(running_errors, "Merge", &tag_issues(conflict_issues));
+                add_issues(running_errors, "Validation", &tag_issues(issues));
+            }
+            Err(err) => running_errors.add_err(
+                &"Schema error".to_string(),
+                format!(
+                    "{} is not a valid JSON schema: {err}",
+                    style_path(&config.schema_json, "schema")
+                ),
+            ),
+        },
+        (Err(err), _) => running_errors.add_err(&"Data error".to_string(), err.to_string()),
+        (_, Err(_)) => {}
+    }
+
+    // script rules still run on data failing the schema, as long as it has the right shape
+    // none are required, since heda init writes no derive or sort, but ones written are checked
+    let rhai_space_opt = RhaiSpace::try_new(
+        &config.scripts_rhai,
+        &config.root_directory,
+        &[],
+        debug,
+        running_errors,
+    );
+    let data_opt = data_json_res
+        .ok()
+        .and_then(|data_json| serde_json::from_value::<Vec<Datum>>(data_json).ok());
+    if let (Some(mut rhai_space), Some(data)) = (rhai_space_opt, &data_opt) {
+        add_issues(
+            running_errors,
+            "Validation",
+            &tag_issues(rhai_space.validate(data)),
+        );
+    }
+    data_opt.map(|data| (data, data_files))
+}
+
+// None when schema.json is up to date, which it is while type.rs has no types yet, like after heda init
+// schemas are compared by their types, since hand written or older ones differ in other ways
+fn get_outdated_changes(
+    schema_json: &serde_json::Value,
+    type_rs: &str,
+) -> Result<Option<Vec<SchemaChange>>, String> {
+    if type_rs.trim().is_empty() {
+        return Ok(None);
+    }
+    let generated_schema = TypeFile::parse(type_rs)?.to_schema()?;
+    if *schema_json == generated_schema {
+        return Ok(None);
+    }
+    Ok(match compare_schemas(schema_json, &generated_schema) {
+        Ok(changes) if changes.is_empty() => None,
+        Ok(changes) => Some(changes),
+        // a schema.json not shaped like a generated one is out of date as a whole
+        Err(_) => Some(Vec::new()),
+    })
+}
+
+fn exit_with_errs(mut running_errors: RunningErrors) -> ! {
+    running_errors.print_errs();
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const TYPE_RS: &str = r#"
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Datum {
+    pub name: String,
+    pub initial: char,
+    #[serde(default)]
+    pub address: Address,
+}
+
+#[derive(Default, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Address {
+    pub city: String,
+}
+"#;
+
+    fn generated_schema() -> serde_json::Value {
+        TypeFile::parse(TYPE_RS).unwrap().to_schema().unwrap()
+    }
+
+    #[test]
+    fn schema_generated_from_type_rs_is_up_to_date() {
+        let changes_opt = get_outdated_changes(&generated_schema(), TYPE_RS).unwrap();
+        assert!(changes_opt.is_none());
+    }
+
+    #[test]
+    fn schema_with_the_same_types_is_up_to_date() {
+        // like one written by schemars::schema_for! with a strict root, and edited docs
+        let mut schema = generated_schema();
+        schema["additionalProperties"] = json!(false);
+        schema["definitions"]["Datum"]["properties"]["name"]["description"] = json!("Full name");
+        let changes_opt = get_outdated_changes(&schema, TYPE_RS).unwrap();
+        assert!(changes_opt.is_none());
+    }
+
+    #[test]
+    fn schema_with_other_types_is_out_of_date() {
+        let type_rs = TYPE_RS.replace("pub initial: char", "pub initial: String");
+        let changes = get_outdated_changes(&generated_schema(), &type_rs)
+            .unwrap()
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "Datum.initial");
+    }
+
+    #[test]
+    fn schema_made_by_init_is_up_to_date_until_type_rs_has_types() {
+        assert!(get_outdated_changes(&json!({}), "").unwrap().is_none());
+        assert!(get_outdated_changes(&json!({}), "\n").unwrap().is_none());
+        let changes = get_outdated_changes(&json!({}), TYPE_RS).unwrap().unwrap();
+        assert!(changes.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::HashSet,
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use crate::shared::{
+    config::{Config, Workspace},
+    datum::Datum,
+    errors::RunningErrors,
+    issues::{assert_no_errors, Issue},
+    json::get_schema_issues,
+    keys::{get_duplicate_groups, get_key_sets},
+    path::style_path,
+    snapshots::make_snapshot,
+};
+
+pub const STRATEGIES: [&str; 3] = ["first", "latest", "merge"];
+
+#[derive(Clone, Copy)]
+enum Strategy {
+    First,
+    Latest,
+    Merge,
+}
+
+impl Strategy {
+    fn from_str(strategy_str: &str) -> Option<Self> {
+        match strategy_str {
+            "first" | "f" => Some(Strategy::First),
+            "latest" | "l" => Some(Strategy::Latest),
+            "merge" | "m" => Some(Strategy::Merge),
+            _ => None,
+        }
+    }
+}
+
+// without a strategy, each group of duplicates is resolved by prompting
+pub fn run_dedupe(config_path: &Path, dataset_arg: Option<&String>, strategy_arg: Option<&String>) {
+    let workspace = Workspace::new(config_path);
+    // clap only allows STRATEGIES, so from_str won't fail
+    let strategy_opt = strategy_arg.and_then(|strategy_str| Strategy::from_str(strategy_str));
+    for config in workspace.select(dataset_arg) {
+        dedupe_dataset(&workspace, config, strategy_opt);
+    }
+}
+
+fn dedupe_dataset(workspace: &Workspace, config: &Config, strategy_opt: Option<Strategy>) {
+    let data_name = style_path(&config.data_json, "data");
+    let key_sets = get_key_sets(config);
+    if key_sets.is_empty() {
+        println!(
+            "{} No {} or {} set for {data_name}",
+            console::style("Warning:").yellow(),
+            console::style("primaryKey").italic(),
+            console::style("uniqueKeys").italic()
+        );
+        return;
+    }
+
+    let mut data = config.read_data();
+    let original_len = data.len();
+    let mut changed = false;
+    for fields in &key_sets {
+        // indexes shift after each removal, so groups are found again for every key set
+        let mut removed_indexes: HashSet<usize> = HashSet::new();
+        for group in get_duplicate_groups(&data, fields) {
+            let strategy = match strategy_opt {
+                Some(strategy) => strategy,
+                None => match prompt_strategy(&data, &group, fields) {
+                    Some(strategy) => strategy,
+                    None => continue,
+                },
+            };
+            data[group[0]] = resolve_group(&data, &group, strategy);
+            removed_indexes.extend(&group[1..]);
+            changed = true;
+        }
+        data = data
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !removed_indexes.contains(index))
+            .map(|(_, datum)| datum)
+            .collect();
+    }
+
+    if !changed {
+        println!(
+            "{} No duplicates resolved in {data_name}",
+            console::style("Success:").green()
+        );
+        return;
+    }
+
+    // merged records could break the schema, which shouldn't be written
+    let new_data_json = serde_json::to_value(&data).expect("turned deduped data to JSON");
+    let issues: Vec<Issue> = get_schema_issues(&new_data_json, &config.schema_json)
+        .into_iter()
+        .map(|issue| issue.in_dataset(&config.name))
+        .collect();
+    assert_no_errors("Validation", &issues);
+    let data_write = config.plan_write_data(&data).unwrap_or_else(|err| {
+        let mut running_errors = RunningErrors::new();
+        running_errors.add_err(&"Data error".to_string(), err);
+        running_errors.print_errs();
+        std::process::exit(1);
+    });
+
+    make_snapshot(workspace);
+    data_write.write();
+    println!(
+        "{} Removed {} duplicates from {data_name}",
+        console::style("Success:").green(),
+        original_len - data.len()
+    );
+}
+
+// the result takes the place of the first record in the group
+fn resolve_group(data: &[Datum], group: &[usize], strategy: Strategy) -> Datum {
+    match strategy {
+        Strategy::First => data[group[0]].clone(),
+        Strategy::Latest => data[group[group.len() - 1]].clone(),
+        // later non-null values win, so fixes made to a pasted copy are kept
+        Strategy::Merge => {
+            let mut merged = data[group[0]].clone();
+            for index in &group[1..] {
+                for (field, value) in &data[*index] {
+                    if !value.is_null() {
+                        merged.insert(field.clone(), value.clone());
+                    }
+                }
+            }
+            merged
+        }
+    }
+}
+
+// None skips the group, including when stdin is closed
+fn prompt_strategy(data: &[Datum], group: &[usize], fields: &[String]) -> Option<Strategy> {
+    println!(
+        "{} records sharing {}:",
+        console::style(group.len()).bold(),
+        console::style(fields.join(", ")).italic()
+    );
+    for index in group {
+        let datum_str =
+            serde_json::to_string_pretty(&data[*index]).expect("turned record to string");
+        println!("{} {datum_str}", console::style(format!("#{index}")).bold());
+    }
+
+    let stdin = std::io::stdin();
+    loop {
+        print!(
+            "Keep {}irst, {}atest, {}erge, or {}kip? ",
+            console::style("[f]").bold(),
+            console::style("[l]").bold(),
+            console::style("[m]").bold(),
+            console::style("[s]").bold()
+        );
+        std::io::stdout()
+            .flush()
+            .expect("Should flush prompt to stdout");
+        let mut line = String::new();
+        let read_count = stdin
+            .lock()
+            .read_line(&mut line)
+            .expect("Should read line from stdin");
+        let answer = line.trim();
+        if read_count == 0 || answer == "s" || answer == "skip" {
+            return None;
+        }
+        if let Some(strategy) = Strategy::from_str(answer) {
+            return Some(strategy);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::shared::{
+    config::{Config, Workspace},
+    data::{combine_data_files, get_data_paths, is_data_path, is_split},
+    datum::Datum,
+    diff::{diff_data, FieldChange, RecordChange},
+    path::style_path,
+    snapshots::Snapshot,
+};
+
+pub const FORMATS: [&str; 3] = ["text", "json", "markdown"];
+
+// a data file, a snapshot in the backups directory, or a git revision, tried in that order
+// with the path of each file read, whose extension is its format
+fn read_source(
+    spec: &str,
+    workspace: &Workspace,
+    config: &Config,
+) -> Result<Vec<(PathBuf, String)>, String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .map(|str| (path.to_path_buf(), str))
+            .map_err(|err| err.to_string())
+    };
+    let path = Path::new(spec);
+    if path.is_file() || path.is_dir() {
+        return get_data_paths(path)?
+            .iter()
+            .map(|data_path| read(data_path))
+            .collect();
+    }
+    let snapshot_path = workspace
+        .backups_directory
+        .join(spec)
+        .with_extension("json");
+    if snapshot_path.is_file() {
+        let snapshot = Snapshot::read(&snapshot_path)?;
+        let data_files = snapshot
+            .read_data_files(&workspace.root_directory, &config.data_json)
+            .ok_or(format!(
+                "{} has no readable copy of {}",
+                style_path(&snapshot_path, "snapshot"),
+                style_path(&config.data_json, "data")
+            ))?;
+        return Ok(data_files
+            .into_iter()
+            .map(|(path, bytes)| (path, String::from_utf8_lossy(&bytes).to_string()))
+            .collect());
+    }
+
+    // like HEAD~1, which is shown at the data file, or HEAD~1:other.json, which is shown as is
+    if let Some((_, revision_path)) = spec.split_once(':') {
+        let data_directory = config.data_json.parent().unwrap_or(Path::new(""));
+        let bytes = git_show(spec, data_directory, spec)?;
+        return Ok(vec![(
+            PathBuf::from(revision_path),
+            String::from_utf8_lossy(&bytes).to_string(),
+        )]);
+    }
+    let revision_paths: Vec<PathBuf> = if is_split(&config.data_json) {
+        // split data is every file of the revision the data path matches, relative to the config
+        let list_bytes = run_git(
+            spec,
+            &workspace.root_directory,
+            &["ls-tree", "-r", "--name-only", spec],
+        )?;
+        String::from_utf8_lossy(&list_bytes)
+            .lines()
+            .map(|name| workspace.root_directory.join(name))
+            .filter(|path| is_data_path(&config.data_json, path))
+            .collect()
+    } else {
+        vec![config.data_json.to_path_buf()]
+    };
+    let mut contents: Vec<(PathBuf, String)> = Vec::new();
+    for revision_path in revision_paths {
+        let directory = revision_path.parent().unwrap_or(Path::new(""));
+        let file_name = revision_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let bytes = git_show(spec, directory, &format!("{spec}:./{file_name}"))?;
+        contents.push((revision_path, String::from_utf8_lossy(&bytes).to_string()));
+    }
+    Ok(contents)
+}
+
+fn git_show(spec: &str, directory: &Path, revision_spec: &str) -> Result<Vec<u8>, String> {
+    run_git(spec, directory, &["show", revision_spec])
+}
+
+fn run_git(spec: &str, directory: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    // git would take it as an option, like --output=<file>
+    if spec.starts_with('-') {
+        return Err(format!("{spec} is not a file, snapshot or git revision"));
+    }
+    let mut git_command = std::process::Command::new("git");
+    if !directory.as_os_str().is_empty() {
+        git_command.arg("-C").arg(directory);
+    }
+    let output = git_command
+        .args(args)
+        .output()
+        .map_err(|err| format!("{spec} is not a file or snapshot, and git could not run: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{spec} is not a file, snapshot or git revision: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+fn read_data(spec: &str, workspace: &Workspace, config: &Config) -> Vec<Datum> {
+    let contents = read_source(spec, workspace, config).unwrap_or_else(|err| panic!("{err}"));
+    // split data is combined from its files, like it's read from the data path
+    let (data_json, _) = combine_data_files(&contents).unwrap_or_else(|err| panic!("{err}"));
+    serde_json::from_value(data_json)
+        .unwrap_or_else(|err| panic!("{spec} should be an array of objects: {err}"))
+}
+
+// the new side defaults to the current data file
+pub fn run_diff(
+    config_path: &Path,
+    dataset_arg: Option<&String>,
+    old_spec: &str,
+    new_spec: Option<&String>,
+    format: &str,
+) {
+    let workspace = Workspace::new(config_path);
+    let configs = workspace.select(dataset_arg);
+    // records are matched by each dataset's own primary key
+    let [config] = configs.as_slice() else {
+        panic!(
+            "Should pick a dataset with {} for a workspace config",
+            console::style("--dataset").bold()
+        );
+    };
+    let old_data = read_data(old_spec, &workspace, config);
+    let new_data = match new_spec {
+        Some(new_spec) => read_data(new_spec, &workspace, config),
+        None => config.read_data(),
+    };
+    let changes = diff_data(&old_data, &new_data, config.primary_key.as_deref());
+
+    let output = match format {
+        "json" => serde_json::to_string_pretty(&changes).expect("turned diff to string"),
+        "markdown" => fmt_markdown(&changes),
+        _ => fmt_text(&changes),
+    };
+    println!("{output}");
+}
+
+fn fmt_value(value_opt: &Option<Value>) -> String {
+    match value_opt {
+        Some(value) => value.to_string(),
+        None => "missing".to_string(),
+    }
+}
+
+fn fmt_summary(changes: &[RecordChange]) -> String {
+    let count = |is_kind: fn(&RecordChange) -> bool| changes.iter().filter(|c| is_kind(c)).count();
+    format!(
+        "{} added, {} removed, {} modified",
+        count(|change| matches!(change, RecordChange::Added { .. })),
+        count(|change| matches!(change, RecordChange::Removed { .. })),
+        count(|change| matches!(change, RecordChange::Modified { .. }))
+    )
+}
+
+fn fmt_text(changes: &[RecordChange]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for change in changes {
+        match change {
+            RecordChange::Added { key, record } => lines.push(format!(
+                "{} {key} {}",
+                console::style("+").green(),
+                console::style(Value::Object(record.clone())).green()
+            )),
+            RecordChange::Removed { key, record } => lines.push(format!(
+                "{} {key} {}",
+                console::style("-").red(),
+                console::style(Value::Object(record.clone())).red()
+            )),
+            RecordChange::Modified { key, fields } => {
+                lines.push(format!("{} {key}", console::style("~").yellow()));
+                for FieldChange { field, old, new } in fields {
+                    lines.push(format!(
+                        "    {field}: {} -> {}",
+                        console::style(fmt_value(old)).red(),
+                        console::style(fmt_value(new)).green()
+                    ));
+                }
+            }
+        }
+    }
+    lines.push(fmt_summary(changes));
+    lines.join("\n")
+}
+
+// pipes would split table cells, so they're escaped
+// backticks inside would end the code span, so it's fenced by a longer run of them
+fn fmt_markdown_cell(str: &str) -> String {
+    let longest_run = str
+        .split(|char| char != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run + 1);
+    // a space keeps backticks at either end from joining the fence, and is trimmed when rendered
+    let padding = if str.starts_with('`') || str.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!(
+        "{fence}{padding}{}{padding}{fence}",
+        str.replace('|', "\\|")
+    )
+}
+
+fn fmt_markdown(changes: &[RecordChange]) -> String {
+    let mut lines: Vec<String> = vec![format!("**{}**", fmt_summary(changes))];
+    for change in changes {
+        lines.push(String::new());
+        match change {
+            RecordChange::Added { record, .. } | RecordChange::Removed { record, .. } => {
+                let verb = match change {
+                    RecordChange::Added { .. } => "Added",
+                    _ => "Removed",
+                };
+                lines.push(format!("### {verb} {}", change.key()));
+                lines.push(String::new());
+                lines.push("```json".to_string());
+                lines.push(serde_json::to_string_pretty(record).expect("turned record to string"));
+                lines.push("```".to_string());
+            }
+            RecordChange::Modified { key, fields } => {
+                lines.push(format!("### Modified {key}"));
+                lines.push(String::new());
+                lines.push("| Field | Old | New |".to_string());
+                lines.push("| --- | --- | --- |".to_string());
+                for FieldChange { field, old, new } in fields {
+                    lines.push(format!(
+                        "| {} | {} | {} |",
+                        fmt_markdown_cell(field),
+                        fmt_markdown_cell(&fmt_value(old)),
+                        fmt_markdown_cell(&fmt_value(new))
+                    ));
+                }
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_cells_fence_backticks_inside() {
+        assert_eq!(fmt_markdown_cell("a"), "`a`");
+        assert_eq!(fmt_markdown_cell("a|b"), "`a\\|b`");
+        assert_eq!(fmt_markdown_cell("a`b"), "``a`b``");
+        assert_eq!(fmt_markdown_cell("a``b`c"), "```a``b`c```");
+        assert_eq!(fmt_markdown_cell("`a`"), "`` `a` ``");
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::shared::{
+    columns::{get_columns, write_cell},
+    config::{Config, Workspace},
+    datum::{get_field, Collections},
+    errors::RunningErrors,
+    infer::infer_type_file,
+    path::style_path,
+    scripts::{from_dynamic, to_dynamic, RhaiSpace, ScriptFn},
+    sqlite::Table,
+};
+
+use super::{
+    import::{read_type_file, select_config},
+    watch::{derive::run_derive, sort::run_sort},
+};
+
+// the table of a dataset, which single dataset configs have no name for
+const DATA_TABLE: &str = "data";
+// derived data across a workspace, next to the ones of each dataset
+const DERIVED_TABLE: &str = "derived";
+
+// columns default to every field of the schema, with nested ones flattened like address.city
+fn get_column_paths(schema_paths: &[String], columns_arg: Option<&String>) -> Vec<String> {
+    let Some(columns_str) = columns_arg else {
+        return schema_paths.to_vec();
+    };
+    let column_paths: Vec<String> = columns_str
+        .split(',')
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty())
+        .collect();
+    // a nested object as a whole is written as JSON
+    for path in &column_paths {
+        let prefix = format!("{path}.");
+        if !schema_paths
+            .iter()
+            .any(|schema_path| schema_path == path || schema_path.starts_with(&prefix))
+        {
+            panic!(
+                "Column {} should be a field of the schema, like {}",
+                console::style(path).bold(),
+                schema_paths.join(", ")
+            );
+        }
+    }
+    column_paths
+}
+
+// writes to stdout without an output path, so it can be piped
+pub fn run_export_csv(
+    config_path: &Path,
+    dataset_arg: Option<&String>,
+    output_path: Option<&Path>,
+    columns_arg: Option<&String>,
+) {
+    let workspace = Workspace::new(config_path);
+    let config = select_config(&workspace, dataset_arg);
+    let schema_paths: Vec<String> = get_columns(&read_type_file(config))
+        .into_iter()
+        .map(|column| column.path)
+        .collect();
+    let column_paths = get_column_paths(&schema_paths, columns_arg);
+    let data = config.read_data();
+
+    let writer: Box<dyn std::io::Write> = match output_path {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .unwrap_or_else(|err| panic!("Should create {}: {err}", style_path(path, "CSV"))),
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer
+        .write_record(&column_paths)
+        .expect("Should write CSV header");
+    for datum in &data {
+        let cells: Vec<String> = column_paths
+            .iter()
+            .map(|path| write_cell(get_field(datum, path)))
+            .collect();
+        csv_writer
+            .write_record(&cells)
+            .expect("Should write CSV row");
+    }
+    csv_writer.flush().expect("Should write CSV");
+
+    if let Some(path) = output_path {
+        println!(
+            "{} Exported {} records from {} to {}",
+            console::style("Success:").green(),
+            data.len(),
+            style_path(&config.data_json, "data"),
+            style_path(path, "CSV")
+        );
+    }
+}
+
+fn get_table_name(config: &Config) -> String {
+    config.name.clone().unwrap_or(DATA_TABLE.to_string())
+}
+
+// foreign keys to datasets that aren't exported are left out, since the table isn't there
+fn get_references(config: &Config, configs: &[&Config]) -> BTreeMap<String, (String, String)> {
+    config
+        .foreign_keys
+        .iter()
+        .filter_map(|(field, dataset)| {
+            let target = configs
+                .iter()
+                .find(|target| target.name.as_ref() == Some(dataset))?;
+            let primary_key = target.primary_key.clone()?;
+            Some((field.clone(), (get_table_name(target), primary_key)))
+        })
+        .collect()
+}
+
+// derived data has no schema, so its table is planned from the types it fits
+fn add_derived_table(
+    tables: &mut Vec<(Table, Vec<Value>)>,
+    rhai_space: &mut RhaiSpace,
+    data: rhai::Dynamic,
+    name: &str,
+    running_errors: &mut RunningErrors,
+) {
+    let mut derived_data = run_derive(rhai_space, data);
+    if rhai_space.has_fn(ScriptFn::Sort) {
+        derived_data = run_sort(rhai_space, derived_data);
+    }
+    let derived_json = from_dynamic::<Value>(&derived_data)
+        .unwrap_or_else(|err| panic!("Should turn derived data for {name} into JSON: {err}"));
+    match infer_type_file(&derived_json) {
+        Ok(type_file) => {
+            let items = derived_json.as_array().cloned().unwrap_or_default();
+            tables.push((Table::new(&type_file, name), items));
+        }
+        Err(err) => running_errors.add_warn(
+            &"Derived data".to_string(),
+            format!("Skipped table {name}, since its derived data can't be a table: {err}"),
+        ),
+    }
+}
+
+// rewritten from scratch each time, so exporting the same data gives the same database
+pub fn run_export_sqlite(
+    config_path: &Path,
+    dataset_arg: Option<&String>,
+    output_path: &Path,
+    derived: bool,
+    debug: bool,
+) {
+    let workspace = Workspace::new(config_path);
+    let configs = workspace.select(dataset_arg);
+    let mut running_errors = RunningErrors::new();
+    let mut tables: Vec<(Table, Vec<Value>)> = Vec::new();
+    let mut collections = Collections::new();
+    for config in &configs {
+        let data = config.read_data();
+        let table_name = get_table_name(config);
+        let table = Table::new(&read_type_file(config), &table_name).with_keys(
+            config.primary_key.as_ref(),
+            get_references(config, &configs),
+        );
+        tables.push((table, data.iter().cloned().map(Value::Object).collect()));
+        if derived {
+            let mut rhai_space =
+                RhaiSpace::new(&config.scripts_rhai, &config.root_directory, &[], debug);
+            if rhai_space.has_fn(ScriptFn::Derive) {
+                add_derived_table(
+                    &mut tables,
+                    &mut rhai_space,
+                    to_dynamic(&data),
+                    &format!("{table_name}_{DERIVED_TABLE}"),
+                    &mut running_errors,
+                );
+            }
+        }
+        if let Some(name) = &config.name {
+            collections.insert(name.clone(), data);
+        }
+    }
+    // scripts across datasets need all of them, so are skipped when one is picked
+    if let (true, Some(scripts_rhai), None) = (derived, &workspace.scripts_rhai, dataset_arg) {
+        let mut rhai_space = RhaiSpace::new(scripts_rhai, &workspace.root_directory, &[], debug);
+        if rhai_space.has_fn(ScriptFn::Derive) {
+            add_derived_table(
+                &mut tables,
+                &mut rhai_space,
+                to_dynamic(&collections),
+                DERIVED_TABLE,
+                &mut running_errors,
+            );
+        }
+    }
+    for (table, _) in &tables {
+        for clash in table.get_name_clashes() {
+            running_errors.add_err(&"SQLite".to_string(), clash);
+        }
+    }
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        std::process::exit(1);
+    }
+
+    // written beside the output then moved over it, so a failed export keeps the last database
+    let partial_path = PathBuf::from(format!("{}.partial", output_path.display()));
+    if partial_path.exists() {
+        std::fs::remove_file(&partial_path).expect(
+            format!(
+                "Should remove partial export {}",
+                style_path(&partial_path, "SQLite")
+            )
+            .as_str(),
+        );
+    }
+    let write_res = (|| -> rusqlite::Result<(usize, usize)> {
+        let mut connection = rusqlite::Connection::open(&partial_path)?;
+        let tx = connection.transaction()?;
+        let (mut table_count, mut row_count) = (0, 0);
+        for (table, items) in &tables {
+            table_count += table.create(&tx, None)?;
+            row_count += table.insert(&tx, items)?;
+        }
+        tx.commit()?;
+        Ok((table_count, row_count))
+    })();
+    let (table_count, row_count) = write_res.unwrap_or_else(|err| {
+        panic!(
+            "Should write {}: {err}",
+            style_path(&partial_path, "SQLite")
+        )
+    });
+    std::fs::rename(&partial_path, output_path).expect(
+        format!(
+            "Should move export to {}",
+            style_path(output_path, "SQLite")
+        )
+        .as_str(),
+    );
+    println!(
+        "{} Exported {row_count} rows in {table_count} tables to {}",
+        console::style("Success:").green(),
+        style_path(output_path, "SQLite")
+    );
+}
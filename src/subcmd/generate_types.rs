@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use crate::shared::{
+    config::Workspace, errors::RunningErrors, json::read_json, path::style_path, type_rs::TypeFile,
+};
+
+// writes types generated from schema.json, for tools that can't read JSON schema
+pub fn run_generate_types(
+    config_path: &Path,
+    dataset_arg: Option<&String>,
+    rust_path: Option<&Path>,
+    typescript_path: Option<&Path>,
+) {
+    if rust_path.is_none() && typescript_path.is_none() {
+        panic!(
+            "Should pass {} or {} to choose what to generate",
+            console::style("--rust").bold(),
+            console::style("--ts").bold()
+        );
+    }
+    let workspace = Workspace::new(config_path);
+    let configs = workspace.select(dataset_arg);
+    // each dataset has its own types, so they can't share an output file
+    let [config] = configs.as_slice() else {
+        panic!(
+            "Should pick a dataset with {} for a workspace config",
+            console::style("--dataset").bold()
+        );
+    };
+
+    let schema = read_json(&config.schema_json);
+    let type_file = TypeFile::from_schema(&schema).unwrap_or_else(|err| {
+        panic!(
+            "Should read types from {}: {err}",
+            style_path(&config.schema_json, "schema")
+        )
+    });
+
+    let mut running_errors = RunningErrors::new();
+    let outputs = [
+        (rust_path, type_file.to_rust(), "type file"),
+        (
+            typescript_path,
+            type_file.to_typescript(),
+            "TypeScript declarations",
+        ),
+    ];
+    for (path_opt, contents, name) in outputs {
+        let Some(path) = path_opt else {
+            continue;
+        };
+        match std::fs::write(path, contents) {
+            Ok(_) => println!(
+                "{} Generated {} from {}",
+                console::style("Success:").green(),
+                style_path(path, name),
+                style_path(&config.schema_json, "schema")
+            ),
+            Err(write_err) => running_errors.add_err(
+                &write_err.to_string(),
+                format!("Could not write file {}.", style_path(path, name)),
+            ),
+        }
+    }
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        std::process::exit(1);
+    }
+}
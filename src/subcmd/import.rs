@@ -0,0 +1,267 @@
+use std::{collections::HashMap, path::Path};
+
+use serde_json::Value;
+
+use crate::shared::{
+    columns::{get_columns, read_cell, Column},
+    config::{Config, Workspace},
+    datum::{set_field, Datum},
+    errors::RunningErrors,
+    issues::{add_issues, Issue},
+    json::{read_json, try_get_schema_issues},
+    keys::get_key_issues,
+    path::style_path,
+    snapshots::make_snapshot,
+    type_rs::TypeFile,
+};
+
+// each dataset has its own schema, so only one can be imported into at a time
+pub fn select_config<'a>(workspace: &'a Workspace, dataset_arg: Option<&String>) -> &'a Config {
+    let configs = workspace.select(dataset_arg);
+    let [config] = configs.as_slice() else {
+        panic!(
+            "Should pick a dataset with {} for a workspace config",
+            console::style("--dataset").bold()
+        );
+    };
+    config
+}
+
+pub fn read_type_file(config: &Config) -> TypeFile {
+    TypeFile::from_schema(&read_json(&config.schema_json)).unwrap_or_else(|err| {
+        panic!(
+            "Should read types from {}: {err}",
+            style_path(&config.schema_json, "schema")
+        )
+    })
+}
+
+// columns are fields of the same name, unless mapped like "Full Name=name"
+pub fn run_import_csv(
+    config_path: &Path,
+    dataset_arg: Option<&String>,
+    csv_path: &Path,
+    map_args: &[String],
+    upsert: bool,
+) {
+    let workspace = Workspace::new(config_path);
+    let config = select_config(&workspace, dataset_arg);
+    let type_file = read_type_file(config);
+    let columns = get_columns(&type_file);
+    if upsert && config.primary_key.is_none() {
+        panic!(
+            "{} needs a {} to match records for {}",
+            style_path(&config.data_json, "data"),
+            console::style("primaryKey").italic(),
+            console::style("--upsert").bold()
+        );
+    }
+
+    let mut running_errors = RunningErrors::new();
+    let mut field_by_column: HashMap<&str, &str> = HashMap::new();
+    for map_arg in map_args {
+        match map_arg.split_once('=') {
+            Some((column, field)) => {
+                field_by_column.insert(column, field);
+            }
+            None => running_errors.add_err(
+                &"Invalid mapping".to_string(),
+                format!("{map_arg} should be like COLUMN=FIELD"),
+            ),
+        }
+    }
+
+    let mut reader = csv::Reader::from_path(csv_path)
+        .unwrap_or_else(|err| panic!("Should read {}: {err}", style_path(csv_path, "CSV")));
+    let headers = reader
+        .headers()
+        .unwrap_or_else(|err| {
+            panic!(
+                "Should read header of {}: {err}",
+                style_path(csv_path, "CSV")
+            )
+        })
+        .clone();
+    // index of each column in the CSV, with the schema column it fills
+    let mut header_columns: Vec<(usize, &Column)> = Vec::new();
+    for (index, header) in headers.iter().enumerate() {
+        let field = field_by_column.get(header).copied().unwrap_or(header);
+        match columns.iter().find(|column| column.path == field) {
+            Some(column) => header_columns.push((index, column)),
+            None => running_errors.add_warn(
+                &"Unknown column".to_string(),
+                format!("Ignored column {header:?}, since {field} is not a field of the schema"),
+            ),
+        }
+    }
+    for (column, field) in &field_by_column {
+        if !headers.iter().any(|header| header == *column) {
+            running_errors.add_err(
+                &"Invalid mapping".to_string(),
+                format!("Mapped column {column:?} to {field}, but the CSV has no such column"),
+            );
+        }
+    }
+
+    // rows are numbered like a spreadsheet, where the header is row 1
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut rows: Vec<(usize, Datum)> = Vec::new();
+    for (row_index, record_res) in reader.records().enumerate() {
+        let row = row_index + 2;
+        let record = match record_res {
+            Ok(record) => record,
+            Err(err) => {
+                issues.push(row_issue(row, None, err.to_string()));
+                continue;
+            }
+        };
+        let mut datum = Datum::new();
+        for (index, column) in &header_columns {
+            let cell = record.get(*index).unwrap_or_default();
+            let cell_res =
+                read_cell(cell, &column.ty, &type_file).and_then(|value_opt| match value_opt {
+                    Some(value) => set_field(&mut datum, &column.path, value),
+                    None => Ok(()),
+                });
+            if let Err(err) = cell_res {
+                issues.push(row_issue(row, Some(&column.path), err));
+            }
+        }
+        rows.push((row, datum));
+    }
+
+    let mut data = config.read_data();
+    let original_len = data.len();
+    let row_by_index = add_rows(config, &mut data, &rows, upsert);
+    let appended_count = data.len() - original_len;
+
+    // validated as the records they end up in, so upserted fields are checked with the rest
+    let new_data_json = serde_json::to_value(&data).expect("turned imported data to JSON");
+    // unwrap is safe, since read_data already validated against the schema
+    let schema_issues =
+        try_get_schema_issues(&new_data_json, &read_json(&config.schema_json)).unwrap();
+    issues.extend(schema_issues.into_iter().filter_map(|issue| {
+        let row = row_by_index.get(&issue.index?)?;
+        Some(row_issue(*row, issue.field.as_deref(), issue.message))
+    }));
+    add_issues(&mut running_errors, "Import", &issues);
+    add_issues(
+        &mut running_errors,
+        "Key",
+        &get_key_issues(&[(config, &data)]),
+    );
+    let data_write_res = config.plan_write_data(&data);
+    if let Err(err) = &data_write_res {
+        running_errors.add_err(&"Data error".to_string(), err.clone());
+    }
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        std::process::exit(1);
+    }
+
+    make_snapshot(&workspace);
+    // unwrap is safe, since errors exited above
+    data_write_res.unwrap().write();
+    println!(
+        "{} Imported {} rows from {} into {}, appending {appended_count} and updating {}",
+        console::style("Success:").green(),
+        rows.len(),
+        style_path(csv_path, "CSV"),
+        style_path(&config.data_json, "data"),
+        rows.len() - appended_count
+    );
+}
+
+// located by CSV row rather than record, which is what the person importing can find
+fn row_issue(row: usize, field: Option<&str>, message: String) -> Issue {
+    let location = match field {
+        Some(field) => format!("row {row}, field {field}"),
+        None => format!("row {row}"),
+    };
+    Issue::error(None, None, format!("{location}: {message}"))
+}
+
+fn find_existing(config: &Config, data: &[Datum], datum: &Datum) -> Option<usize> {
+    // unwrap is safe, since upserting requires a primary key
+    let primary_key = config.primary_key.as_ref().unwrap();
+    let key = datum.get(primary_key).filter(|value| !value.is_null())?;
+    data.iter()
+        .position(|existing| existing.get(primary_key) == Some(key))
+}
+
+// upserted rows only replace the fields they have, leaving the rest of the record
+// returns which CSV row each added or updated record came from, to report issues by row
+fn add_rows(
+    config: &Config,
+    data: &mut Vec<Datum>,
+    rows: &[(usize, Datum)],
+    upsert: bool,
+) -> HashMap<usize, usize> {
+    let mut row_by_index: HashMap<usize, usize> = HashMap::new();
+    for (row, datum) in rows {
+        let existing_opt = if upsert {
+            find_existing(config, data, datum)
+        } else {
+            None
+        };
+        match existing_opt {
+            Some(existing_index) => {
+                merge_fields(&mut data[existing_index], datum);
+                row_by_index.insert(existing_index, *row);
+            }
+            None => {
+                row_by_index.insert(data.len(), *row);
+                data.push(datum.clone());
+            }
+        }
+    }
+    row_by_index
+}
+
+// nested objects are merged too, so a column like address.city keeps the rest of the address
+fn merge_fields(existing: &mut Datum, datum: &Datum) {
+    for (field, value) in datum {
+        match (existing.get_mut(field), value) {
+            (Some(Value::Object(existing_inner)), Value::Object(inner)) => {
+                merge_fields(existing_inner, inner)
+            }
+            _ => {
+                existing.insert(field.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn to_datum(value: Value) -> Datum {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn upserted_columns_keep_the_rest_of_the_record() {
+        let mut existing = to_datum(json!({
+            "id": 1,
+            "name": "a",
+            "address": { "city": "x", "zip": "1" },
+            "tags": ["p", "q"]
+        }));
+        merge_fields(
+            &mut existing,
+            &to_datum(json!({ "id": 1, "address": { "city": "y" }, "tags": ["r"] })),
+        );
+        assert_eq!(
+            existing,
+            to_datum(json!({
+                "id": 1,
+                "name": "a",
+                "address": { "city": "y", "zip": "1" },
+                "tags": ["r"]
+            }))
+        );
+    }
+}
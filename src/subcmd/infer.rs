@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use crate::shared::{
+    data::read_data_file, errors::RunningErrors, infer::infer_type_file, path::style_path,
+};
+
+// type.rs and schema.json contents for existing data, with schema.json generated from type.rs
+pub fn get_inferred_files(data_path: &Path) -> (String, String) {
+    let data_json = read_data_file(data_path);
+    let type_file = infer_type_file(&data_json).unwrap_or_else(|err| {
+        panic!(
+            "Should infer types from {}: {err}",
+            style_path(data_path, "data")
+        )
+    });
+    // unwrap is safe, since inferred types only reference types defined alongside them
+    let schema = type_file.to_schema().unwrap();
+    let schema_str = serde_json::to_string_pretty(&schema).expect("turned schema to string");
+    (type_file.to_rust(), schema_str)
+}
+
+pub fn run_infer(data_path: &Path, type_path: &Path, schema_path: &Path) {
+    let (type_str, schema_str) = get_inferred_files(data_path);
+
+    // inferring is for new projects, so existing files are never overwritten
+    let mut running_errors = RunningErrors::new();
+    for (path, contents, name) in [
+        (type_path, type_str, "type file"),
+        (schema_path, schema_str, "schema"),
+    ] {
+        if path.exists() {
+            running_errors.add_err(
+                &"File already exists".to_string(),
+                format!("Did not overwrite {}.", style_path(path, name)),
+            );
+            continue;
+        }
+        if let Err(write_err) = std::fs::write(path, contents) {
+            running_errors.add_err(
+                &write_err.to_string(),
+                format!("Could not write file {}.", style_path(path, name)),
+            );
+        }
+    }
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        std::process::exit(1);
+    }
+    println!(
+        "{} Inferred {} and {} from {}",
+        console::style("Success:").green(),
+        style_path(type_path, "type file"),
+        style_path(schema_path, "schema"),
+        style_path(data_path, "data")
+    );
+}
@@ -1,6 +1,8 @@
 use std::path::Path;
 
-use crate::shared::{errors::RunningErrors, path::style_path};
+use crate::shared::{data::read_data_file, errors::RunningErrors, path::style_path};
+
+use super::infer::get_inferred_files;
 
 // relative paths to add to base_path and create default files / directories
 const PATH_CONFIG: &str = "./config.json";
@@ -30,7 +32,23 @@ const DEFAULT_SCRIPTS: &str = "";
 // TODO
 const DEFAULT_TYPE: &str = "";
 
-pub fn run_init(base_path: &Path) {
+// with from_path, the project starts with that data and types inferred from it
+pub fn run_init(base_path: &Path, from_path: Option<&Path>) {
+    let (data_str, type_str, schema_str) = match from_path {
+        Some(from_path) => {
+            // data.json is JSON, whatever format the data came in
+            let data_str = serde_json::to_string_pretty(&read_data_file(from_path))
+                .expect("turned data to string");
+            let (type_str, schema_str) = get_inferred_files(from_path);
+            (data_str, type_str, schema_str)
+        }
+        None => (
+            DEFAULT_DATA.to_string(),
+            DEFAULT_TYPE.to_string(),
+            DEFAULT_SCHEMA.to_string(),
+        ),
+    };
+
     let mut running_errors = RunningErrors::new();
 
     // should we handle every err type to make it read better?
@@ -46,10 +64,10 @@ pub fn run_init(base_path: &Path) {
     };
 
     create_file(PATH_CONFIG, DEFAULT_CONFIG);
-    create_file(PATH_DATA, DEFAULT_DATA);
-    create_file(PATH_SCHEMA, DEFAULT_SCHEMA);
+    create_file(PATH_DATA, &data_str);
+    create_file(PATH_SCHEMA, &schema_str);
     create_file(PATH_SCRIPTS, DEFAULT_SCRIPTS);
-    create_file(PATH_TYPE, DEFAULT_TYPE);
+    create_file(PATH_TYPE, &type_str);
 
     let mut create_dir = |rel_path: &str| {
         let path = base_path.join(rel_path);
@@ -0,0 +1,7 @@
+use std::path::Path;
+
+use crate::shared::{migration::MigrationJsonSchema, schema::write_schema};
+
+pub fn make_migration_schema(path: &Path) {
+    write_schema::<MigrationJsonSchema>(path);
+}
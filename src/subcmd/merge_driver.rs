@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use crate::shared::{
+    config::{Config, Workspace},
+    data::{get_data_paths, get_records, DataFormat},
+    datum::Datum,
+    errors::RunningErrors,
+    issues::{add_issues, Issue},
+    json::{read_json, try_get_schema_issues},
+    merge::{get_conflict_issues, merge_data, without_conflicted},
+    path::style_path,
+};
+
+// git's copies of each side have no extension, so are read in the format of the merged file
+// a file of split data may hold a single record, which is merged as an array of one
+fn read_side(path: &Path, side: &str, format: DataFormat) -> (Vec<Datum>, bool) {
+    let str = std::fs::read_to_string(path)
+        .expect(format!("Should read {} for {side}", style_path(path, "data")).as_str());
+    let data_json = format.parse(&str).unwrap_or_else(|err| {
+        panic!(
+            "Should parse {} for {side} as {}: {err}",
+            style_path(path, "data"),
+            format.name()
+        )
+    });
+    let records_opt = get_records(data_json).and_then(|(records, is_record)| {
+        let data = serde_json::from_value(serde_json::Value::Array(records)).ok()?;
+        Some((data, is_record))
+    });
+    records_opt.unwrap_or_else(|| {
+        panic!(
+            "{} should be an array of objects for {side}",
+            style_path(path, "data")
+        )
+    })
+}
+
+// git passes the path being merged, which picks the dataset in a workspace
+fn find_config<'a>(
+    workspace: &'a Workspace,
+    dataset_arg: Option<&String>,
+    merged_path: Option<&Path>,
+) -> &'a Config {
+    if let Some(merged_path) = merged_path {
+        let current_dir = std::env::current_dir().expect("Should get current directory");
+        let canonical = |path: &Path| std::fs::canonicalize(current_dir.join(path)).ok();
+        let merged_canonical = canonical(merged_path);
+        if let Some(config) = workspace.datasets.iter().find(|config| {
+            // split data is matched by any of its files
+            let data_paths = get_data_paths(&config.data_json).unwrap_or_default();
+            merged_canonical.is_some()
+                && data_paths
+                    .iter()
+                    .any(|data_path| canonical(data_path) == merged_canonical)
+        }) {
+            return config;
+        }
+    }
+    let configs = workspace.select(dataset_arg);
+    let [config] = configs.as_slice() else {
+        panic!(
+            "Should pick a dataset with {} for a workspace config",
+            console::style("--dataset").bold()
+        );
+    };
+    config
+}
+
+// writes the merge into ours, like git expects, failing when it has conflicts
+pub fn run_merge_driver(
+    config_path: &Path,
+    dataset_arg: Option<&String>,
+    base_path: &Path,
+    our_path: &Path,
+    their_path: &Path,
+    merged_path: Option<&Path>,
+) {
+    let workspace = Workspace::new(config_path);
+    let config = find_config(&workspace, dataset_arg, merged_path);
+    let format = DataFormat::from_path(merged_path.unwrap_or(&config.data_json));
+    let (base_data, _) = read_side(base_path, "base", format);
+    let (our_data, is_record) = read_side(our_path, "ours", format);
+    let (their_data, _) = read_side(their_path, "theirs", format);
+    let (merged_data, conflict_count) = merge_data(
+        &base_data,
+        &our_data,
+        &their_data,
+        config.primary_key.as_deref(),
+    );
+    let merged_json = serde_json::to_value(&merged_data).expect("turned merged data to JSON");
+    let written_json = match (is_record, merged_json.as_array().map(Vec::as_slice)) {
+        (true, Some([record])) => record.clone(),
+        _ => merged_json.clone(),
+    };
+    let merged_str = format
+        .to_string(&written_json)
+        .unwrap_or_else(|err| panic!("Should write merge as {}: {err}", format.name()));
+    std::fs::write(our_path, merged_str)
+        .expect(format!("Should write merge to {}", style_path(our_path, "data")).as_str());
+
+    // conflicts fail the schema anyway, so only the rest of the data is validated
+    let conflict_issues = get_conflict_issues(&merged_json);
+    let schema_issues: Vec<Issue> = without_conflicted(
+        match try_get_schema_issues(&merged_json, &read_json(&config.schema_json)) {
+            Ok(issues) => issues,
+            Err(err) => panic!(
+                "{} should be a valid JSON schema: {err}",
+                style_path(&config.schema_json, "schema")
+            ),
+        },
+        &conflict_issues,
+    );
+
+    let mut running_errors = RunningErrors::new();
+    add_issues(&mut running_errors, "Merge", &conflict_issues);
+    add_issues(&mut running_errors, "Validation", &schema_issues);
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        if conflict_count > 0 {
+            eprintln!(
+                "{} conflicts left in {}; resolve each {} then run heda check",
+                conflict_count,
+                style_path(our_path, "data"),
+                console::style("\"$conflict\"").magenta()
+            );
+        } else {
+            eprintln!(
+                "Merged {} without conflicts, but it fails validation; fix it then run heda check",
+                style_path(our_path, "data")
+            );
+        }
+        std::process::exit(1);
+    }
+    println!(
+        "{} Merged {} records into {}",
+        console::style("Success:").green(),
+        merged_data.len(),
+        style_path(our_path, "data")
+    );
+}
@@ -1,41 +1,386 @@
 use std::path::Path;
 
 use crate::shared::{
-    backups::get_backup_path,
-    config::Config,
+    config::{Config, Workspace},
+    data::DataWrite,
     datum::Datum,
-    json::validate_json,
+    errors::RunningErrors,
+    evolution::{compare_schemas, has_breaking, ChangeKind, SchemaChange},
+    issues::{add_issues, Issue},
+    journal::{last_entry, push_entry, remove_entry, JournalEntry},
+    json::{read_json, try_get_schema_issues},
+    migration::{apply_steps, has_script_step, read_migration, MigrationStep},
     path::style_path,
-    scripts::{RhaiSpace, ScriptFn},
+    scripts::{from_dynamic, to_dynamic, RhaiSpace, ScriptFn},
+    snapshots::{make_snapshot, Snapshot},
+    type_rs::TypeFile,
 };
 
-pub fn run_migrate(config_path: &Path) {
-    let config = Config::new(config_path);
+// steps from a declarative migration file replace the migrate script, unless they call it
+pub fn run_migrate(
+    config_path: &Path,
+    dataset_arg: Option<&String>,
+    steps_path: Option<&Path>,
+    dry_run: bool,
+    debug: bool,
+) {
+    let workspace = Workspace::new(config_path);
+    let steps_opt = steps_path.map(read_migration);
+    // every dataset is migrated and checked before any is written, so a failure leaves none migrated
+    let mut running_errors = RunningErrors::new();
+    let mut planned_migrations: Vec<PlannedMigration> = Vec::new();
+    for config in workspace.select(dataset_arg) {
+        let planned_opt = plan_dataset(
+            config,
+            steps_opt.as_deref(),
+            dry_run,
+            debug,
+            &mut running_errors,
+        );
+        planned_migrations.extend(planned_opt);
+    }
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        std::process::exit(1);
+    }
+    if planned_migrations.is_empty() {
+        return;
+    }
+
+    // one snapshot has every dataset before the migration, for undoing each
+    let snapshot_directory = make_snapshot(&workspace);
+    for planned in planned_migrations {
+        let config = planned.config;
+        planned.data_write.write();
+        let new_schema_str = serde_json::ser::to_string_pretty(&planned.new_schema)
+            .expect("turned new schema to string");
+        std::fs::write(&config.schema_json, &new_schema_str).expect("replaced old schema");
+        push_entry(
+            &config.backups_directory,
+            JournalEntry::new(config, &snapshot_directory),
+        );
+        println!(
+            "{} Migrated {} and {}",
+            console::style("Success:").green(),
+            style_path(&config.data_json, "data"),
+            style_path(&config.schema_json, "schema")
+        );
+    }
+}
+
+// the files a dataset's migration writes, once it's known to succeed
+struct PlannedMigration<'a> {
+    config: &'a Config,
+    data_write: DataWrite,
+    new_schema: serde_json::Value,
+}
+
+// type.rs is the source of truth, so schema.json is regenerated from it alongside the data
+// None when there's nothing to write, including for a dry run or a dataset with errors
+fn plan_dataset<'a>(
+    config: &'a Config,
+    steps_opt: Option<&[MigrationStep]>,
+    dry_run: bool,
+    debug: bool,
+    all_running_errors: &mut RunningErrors,
+) -> Option<PlannedMigration<'a>> {
+    let old_schema = read_json(&config.schema_json);
+    let new_schema = TypeFile::read(&config.type_rs)
+        .and_then(|type_file| type_file.to_schema())
+        .unwrap_or_else(|err| panic!("{err}"));
+    // like the {} schema made by heda init, which any change breaks
+    let changes = compare_schemas(&old_schema, &new_schema).unwrap_or_else(|err| {
+        vec![SchemaChange {
+            kind: ChangeKind::Breaking,
+            path: style_path(&config.schema_json, "schema").to_string(),
+            message: format!(
+                "could not be compared to {}: {err}",
+                style_path(&config.type_rs, "type file")
+            ),
+        }]
+    });
+    for change in &changes {
+        println!("{}", change.fmt());
+    }
+
+    let required_fns = match steps_opt {
+        Some(steps) if has_script_step(steps) => vec![ScriptFn::Migrate],
+        _ => Vec::new(),
+    };
+    let mut rhai_space = RhaiSpace::new(
+        &config.scripts_rhai,
+        &config.root_directory,
+        &required_fns,
+        debug,
+    );
+    let data = config.read_validated_data(&mut rhai_space);
+
+    let has_migration = steps_opt.is_some() || rhai_space.has_fn(ScriptFn::Migrate);
+    if !has_migration && old_schema == new_schema {
+        println!(
+            "{} Nothing to migrate for {}",
+            console::style("Success:").green(),
+            style_path(&config.data_json, "data")
+        );
+        return None;
+    }
+    let new_data_res = match steps_opt {
+        Some(steps) => apply_steps(steps, &data, &mut rhai_space),
+        None if has_migration => run_migration(&mut rhai_space, &data),
+        None => Ok(data.clone()),
+    };
+    let new_data = match new_data_res {
+        Ok(new_data) => new_data,
+        Err(issues) => {
+            let issues: Vec<Issue> = issues
+                .into_iter()
+                .map(|issue| issue.in_dataset(&config.name))
+                .collect();
+            add_issues(all_running_errors, "Migration", &issues);
+            return None;
+        }
+    };
+
+    // records failing the new schema are reported, since they're what a migration must fix
+    let new_data_json = serde_json::to_value(&new_data).expect("turned migration result to JSON");
+    // unwrap is safe, since the schema was generated from type.rs
+    let new_issues: Vec<Issue> = try_get_schema_issues(&new_data_json, &new_schema)
+        .unwrap()
+        .into_iter()
+        .map(|issue| issue.in_dataset(&config.name))
+        .collect();
+    let mut running_errors = RunningErrors::new();
+    // without any records, nothing can break
+    if !has_migration && has_breaking(&changes) && !new_data.is_empty() {
+        running_errors.add_err(
+            &"Breaking schema change".to_string(),
+            format!(
+                "{} has breaking changes, so needs a {} function in {}, or {}",
+                style_path(&config.type_rs, "type file"),
+                console::style("migrate").magenta(),
+                style_path(&config.scripts_rhai, "scripts"),
+                console::style("--steps").bold()
+            ),
+        );
+    }
+    add_issues(&mut running_errors, "Migration", &new_issues);
+    let data_write_res = config.plan_write_data(&new_data);
+    match &data_write_res {
+        Ok(data_write) => {
+            for yaml_path in data_write.yaml_paths() {
+                running_errors.add_warn(
+                    &"YAML comments".to_string(),
+                    format!(
+                        "Comments in {} will be lost, since it's rewritten from its data.",
+                        style_path(yaml_path, "data")
+                    ),
+                );
+            }
+        }
+        Err(err) => running_errors.add_err(&"Data error".to_string(), err.clone()),
+    }
+    let has_errs = running_errors.has_errs();
+    all_running_errors.extend(running_errors);
+    if has_errs {
+        return None;
+    }
 
-    // make backup
-    let backup_path = get_backup_path(&config.data_json);
-    std::fs::copy(&config.data_json, &backup_path).expect(
+    if dry_run {
+        let changed_count = data
+            .iter()
+            .zip(&new_data)
+            .filter(|(datum, new_datum)| datum != new_datum)
+            .count();
+        println!(
+            "{} Dry run would change {changed_count} of {} records in {}{}",
+            console::style("Success:").green(),
+            new_data.len(),
+            style_path(&config.data_json, "data"),
+            if old_schema == new_schema {
+                String::new()
+            } else {
+                format!(
+                    " and regenerate {}",
+                    style_path(&config.schema_json, "schema")
+                )
+            }
+        );
+        return None;
+    }
+
+    // unwrap is safe, since errors returned above
+    Some(PlannedMigration {
+        config,
+        data_write: data_write_res.unwrap(),
+        new_schema,
+    })
+}
+
+// restores files from the backups of each dataset's most recent migration
+pub fn run_undo(config_path: &Path, dataset_arg: Option<&String>) {
+    let workspace = Workspace::new(config_path);
+    let mut running_errors = RunningErrors::new();
+    for config in workspace.select(dataset_arg) {
+        let Some((entry, previous_entry)) = last_entry(config) else {
+            running_errors.add_err(
+                &"No migration to undo".to_string(),
+                format!(
+                    "{} has no migrations in {}.",
+                    style_path(&config.data_json, "data"),
+                    style_path(&config.backups_directory, "backups directory")
+                ),
+            );
+            continue;
+        };
+        undo_dataset(
+            &workspace,
+            config,
+            &entry,
+            previous_entry.as_ref(),
+            &mut running_errors,
+        );
+    }
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        std::process::exit(1);
+    }
+}
+
+fn undo_dataset(
+    workspace: &Workspace,
+    config: &Config,
+    entry: &JournalEntry,
+    previous_entry: Option<&JournalEntry>,
+    running_errors: &mut RunningErrors,
+) {
+    let snapshot = match Snapshot::read(&config.backups_directory.join(&entry.snapshot)) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            running_errors.add_err(
+                &"Damaged snapshot".to_string(),
+                format!(
+                    "Could not undo migration of {}: {err}",
+                    style_path(&config.data_json, "data")
+                ),
+            );
+            return;
+        }
+    };
+    let (Some(old_data_files), Some(old_schema_bytes)) = (
+        snapshot.read_data_files(&workspace.root_directory, &config.data_json),
+        snapshot.read_file(&workspace.root_directory, &config.schema_json),
+    ) else {
+        running_errors.add_err(
+            &"Damaged snapshot".to_string(),
+            format!(
+                "Could not undo migration of {}, since {} is missing its files or they are corrupted.",
+                style_path(&config.data_json, "data"),
+                style_path(&snapshot.path, "snapshot")
+            ),
+        );
+        return;
+    };
+
+    // the type file is only restored when the migration changed the schema
+    let old_schema: serde_json::Value = serde_json::from_slice(&old_schema_bytes).expect(
         format!(
-            "Should copy backup of {} to {}",
-            style_path(&config.data_json, "data JSON"),
-            style_path(&backup_path, "backups directory")
+            "Schema in {} should be JSON",
+            style_path(&snapshot.path, "snapshot")
         )
         .as_str(),
     );
+    let schema_changed = old_schema != read_json(&config.schema_json);
+    let old_type_bytes = if !schema_changed {
+        None
+    } else if let Some(previous_type_bytes) = previous_entry
+        .and_then(|previous_entry| {
+            Snapshot::read(&config.backups_directory.join(&previous_entry.snapshot)).ok()
+        })
+        .and_then(|previous_snapshot| {
+            previous_snapshot.read_file(&workspace.root_directory, &config.type_rs)
+        })
+    {
+        Some(previous_type_bytes)
+    } else {
+        // without an earlier migration, the old type file is generated from the old schema
+        match TypeFile::from_schema(&old_schema) {
+            Ok(type_file) => Some(type_file.to_rust().into_bytes()),
+            Err(err) => {
+                running_errors.add_warn(
+                    &err,
+                    format!(
+                        "Did not restore {}, since it could not be generated from {}.",
+                        style_path(&config.type_rs, "type file"),
+                        style_path(&snapshot.path, "snapshot")
+                    ),
+                );
+                None
+            }
+        }
+    };
+    if old_type_bytes.is_some()
+        && snapshot.read_file(&workspace.root_directory, &config.type_rs)
+            != std::fs::read(&config.type_rs).ok()
+    {
+        running_errors.add_warn(
+            &"Type file edited".to_string(),
+            format!(
+                "{} changed since the migration, so it was backed up before restoring.",
+                style_path(&config.type_rs, "type file")
+            ),
+        );
+    }
 
-    // migrate
-    let mut rhai_space = RhaiSpace::new(&config.scripts_rhai);
-    let data = validate_json::<Vec<Datum>>(&config.data_json, &config.schema_json);
-    let mut new_data: Vec<Datum> = Vec::new();
-    for datum in data {
-        let fn_res: rhai::Dynamic =
-            rhai_space.call_fn::<rhai::Dynamic>(ScriptFn::Migrate, (datum,));
-        let new_datum = fn_res.cast::<Datum>();
-        new_data.push(new_datum);
+    // the migrated project is snapshotted too, so undoing can itself be undone with heda restore
+    make_snapshot(workspace);
+    for (path, bytes) in old_data_files {
+        std::fs::write(&path, bytes)
+            .expect(format!("Should restore {}", style_path(&path, "data")).as_str());
     }
-    let new_data_str =
-        serde_json::ser::to_string_pretty(&new_data).expect("turned migration result to string");
-    std::fs::write(&config.data_json, &new_data_str).expect("replaced old data");
+    snapshot.remove_added_data_files(&workspace.root_directory, &config.data_json);
+    std::fs::write(&config.schema_json, old_schema_bytes).expect("restored old schema");
+    if let Some(type_bytes) = old_type_bytes {
+        std::fs::write(&config.type_rs, type_bytes).expect("restored old type file");
+    }
+    remove_entry(config, entry);
+    println!(
+        "{} Undid migration of {} from {}",
+        console::style("Success:").green(),
+        style_path(&config.data_json, "data"),
+        entry.migratedAt
+    );
+}
 
-    // TODO make new type somewhere??
+// every record is migrated, so each one the script fails on is reported
+fn run_migration(rhai_space: &mut RhaiSpace, data: &[Datum]) -> Result<Vec<Datum>, Vec<Issue>> {
+    let mut new_data: Vec<Datum> = Vec::new();
+    let mut issues: Vec<Issue> = Vec::new();
+    for (index, datum) in data.iter().enumerate() {
+        let fn_res = rhai_space.call_fn_at::<rhai::Dynamic>(
+            ScriptFn::Migrate,
+            Some(index),
+            (to_dynamic(datum),),
+        );
+        let new_datum_res = fn_res.and_then(|fn_value| {
+            from_dynamic::<Datum>(&fn_value).map_err(|_| {
+                Issue::error(
+                    Some(index),
+                    None,
+                    format!(
+                        "{} fn should return an object",
+                        console::style("migrate").magenta()
+                    ),
+                )
+            })
+        });
+        match new_datum_res {
+            Ok(new_datum) => new_data.push(new_datum),
+            Err(issue) => issues.push(issue),
+        }
+    }
+    if issues.is_empty() {
+        Ok(new_data)
+    } else {
+        Err(issues)
+    }
 }
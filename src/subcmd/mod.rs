@@ -1,4 +1,16 @@
+pub mod check;
+pub mod dedupe;
+pub mod diff;
+pub mod export;
+pub mod generate_types;
+pub mod import;
+pub mod infer;
 pub mod init;
 pub mod make_config_schema;
+pub mod make_migration_schema;
+pub mod merge_driver;
 pub mod migrate;
+pub mod repl;
+pub mod restore;
+pub mod test;
 pub mod watch;
@@ -0,0 +1,154 @@
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use crate::shared::{
+    config::Workspace,
+    datum::Collections,
+    scripts::{from_dynamic, to_dynamic, RhaiSpace},
+};
+
+const DATA_VAR: &str = "data";
+
+pub fn run_repl(config_path: &Path, dataset_arg: Option<&String>, debug: bool) {
+    let workspace = Workspace::new(config_path);
+    let mut rhai_space = load_space(&workspace, dataset_arg, debug);
+    let loaded_str = match (workspace.is_workspace(), dataset_arg) {
+        (true, None) => "every dataset".to_string(),
+        _ => workspace.select(dataset_arg)[0]
+            .data_json
+            .to_string_lossy()
+            .to_string(),
+    };
+    println!(
+        "Loaded {} into {}. Type {} for commands.",
+        console::style(loaded_str).cyan(),
+        console::style(DATA_VAR).italic(),
+        console::style(":help").bold()
+    );
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("{} ", console::style("heda>").bold());
+        std::io::stdout()
+            .flush()
+            .expect("Should flush prompt to stdout");
+        let line = match lines.next() {
+            Some(line_res) => line_res.expect("Should read line from stdin"),
+            None => break,
+        };
+        let input = line.trim();
+
+        match input {
+            "" => continue,
+            ":quit" | ":exit" => break,
+            ":help" => print_help(),
+            ":reload" => {
+                // the scripts or data may be broken mid-edit, so keep the old space on failure
+                let reload_res =
+                    std::panic::catch_unwind(|| load_space(&workspace, dataset_arg, debug));
+                match reload_res {
+                    Ok(new_space) => {
+                        rhai_space = new_space;
+                        println!("{} Reloaded", console::style("Success:").green());
+                    }
+                    Err(_) => println!(
+                        "{} Reload failed; keeping previous scripts and data",
+                        console::style("Warning:").yellow()
+                    ),
+                }
+            }
+            _ => eval_input(&mut rhai_space, input),
+        }
+    }
+}
+
+// runs top level statements once, so constants and imports are available to the session
+fn load_space<'a>(
+    workspace: &Workspace,
+    dataset_arg: Option<&String>,
+    debug: bool,
+) -> RhaiSpace<'a> {
+    // a workspace without --dataset loads its cross-dataset scripts, with every collection
+    let (scripts_rhai, data) = match (workspace.is_workspace(), dataset_arg) {
+        (true, None) => {
+            let scripts_rhai = workspace.scripts_rhai.as_ref().expect(
+                format!(
+                    "Should have {} in a workspace config, or pick one with {}",
+                    console::style("scriptsRhai").italic(),
+                    console::style("--dataset").bold()
+                )
+                .as_str(),
+            );
+            let collections: Collections = workspace
+                .datasets
+                .iter()
+                .map(|config| (config.name.clone().unwrap_or_default(), config.read_data()))
+                .collect();
+            (scripts_rhai, to_dynamic(&collections))
+        }
+        _ => {
+            let config = workspace.select(dataset_arg)[0];
+            (&config.scripts_rhai, to_dynamic(&config.read_data()))
+        }
+    };
+    let mut rhai_space = RhaiSpace::new(scripts_rhai, &workspace.root_directory, &[], debug);
+    rhai_space.show_output();
+    rhai_space.scope.push(DATA_VAR, data);
+    rhai_space
+        .engine
+        .run_ast_with_scope(&mut rhai_space.scope, &rhai_space.ast)
+        .expect("Should run top level statements of scripts");
+    rhai_space
+}
+
+fn eval_input(rhai_space: &mut RhaiSpace, input: &str) {
+    let input_ast = match rhai_space
+        .engine
+        .compile_with_scope(&rhai_space.scope, input)
+    {
+        Ok(input_ast) => input_ast,
+        Err(err) => {
+            eprintln!("{} {}", console::style("Error:").red(), err);
+            return;
+        }
+    };
+    // script functions are callable, but top level statements are not run again
+    let ast = rhai_space.ast.clone_functions_only().merge(&input_ast);
+    let eval_res = rhai_space
+        .engine
+        .eval_ast_with_scope::<rhai::Dynamic>(&mut rhai_space.scope, &ast);
+    match eval_res {
+        Ok(value) => {
+            if !value.is_unit() {
+                println!("{}", fmt_value(&value));
+            }
+        }
+        Err(err) => eprintln!("{} {}", console::style("Error:").red(), err),
+    }
+}
+
+fn fmt_value(value: &rhai::Dynamic) -> String {
+    match from_dynamic::<serde_json::Value>(value) {
+        Ok(json) => serde_json::to_string_pretty(&json).unwrap_or_else(|_| value.to_string()),
+        Err(_) => value.to_string(),
+    }
+}
+
+fn print_help() {
+    println!(
+        "\
+Evaluate rhai expressions against {data}, using any function in the scripts file.
+  {derive}\tcall a script function, with results shown as JSON
+  {reload}\treload the scripts and data files
+  {help}\tshow this message
+  {quit}\texit the REPL",
+        data = console::style(DATA_VAR).italic(),
+        derive = console::style("derive(data)").italic(),
+        reload = console::style(":reload").bold(),
+        help = console::style(":help").bold(),
+        quit = console::style(":quit").bold(),
+    );
+}
@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use crate::shared::{
+    config::Workspace,
+    errors::RunningErrors,
+    path::style_path,
+    snapshots::{get_unused_blobs, make_snapshot, Snapshot},
+};
+
+pub fn run_list_snapshots(config_path: &Path) {
+    let workspace = Workspace::new(config_path);
+    let (snapshots, errs) = Snapshot::list(&workspace.backups_directory);
+    let mut running_errors = RunningErrors::new();
+    for err in errs {
+        running_errors.add_warn(&"Damaged snapshot".to_string(), err);
+    }
+    running_errors.print_errs();
+    if snapshots.is_empty() {
+        println!(
+            "No snapshots in {}",
+            style_path(&workspace.backups_directory, "backups directory")
+        );
+    }
+    for snapshot in snapshots {
+        println!(
+            "{} {} files, made {} by heda {}",
+            console::style(snapshot.name()).blue(),
+            snapshot.manifest.files.len(),
+            snapshot.manifest.createdAt,
+            snapshot.manifest.hedaVersion
+        );
+    }
+}
+
+// restores every file of a snapshot, the most recent one if none is named
+pub fn run_restore(config_path: &Path, snapshot_arg: Option<&String>) {
+    let workspace = Workspace::new(config_path);
+    let mut running_errors = RunningErrors::new();
+    let snapshot_res = match snapshot_arg {
+        Some(snapshot_str) => {
+            // a name in the backups directory, or a path to a snapshot anywhere
+            let named_path = workspace
+                .backups_directory
+                .join(snapshot_str)
+                .with_extension("json");
+            let path = if named_path.is_file() {
+                named_path
+            } else {
+                Path::new(snapshot_str).to_path_buf()
+            };
+            Snapshot::read(&path)
+        }
+        None => {
+            // a damaged snapshot might be the latest, which restoring an older one should mention
+            let (mut snapshots, errs) = Snapshot::list(&workspace.backups_directory);
+            for err in errs {
+                running_errors.add_warn(&"Damaged snapshot".to_string(), err);
+            }
+            snapshots.pop().ok_or(format!(
+                "Should have a snapshot in {}",
+                style_path(&workspace.backups_directory, "backups directory")
+            ))
+        }
+    };
+    let snapshot = match snapshot_res {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            running_errors.add_err(&"Damaged snapshot".to_string(), err);
+            running_errors.print_errs();
+            std::process::exit(1);
+        }
+    };
+
+    // a partial restore would leave files that don't belong together
+    add_damaged_errs(&mut running_errors, &snapshot);
+    if snapshot.manifest.hedaVersion != env!("CARGO_PKG_VERSION") {
+        running_errors.add_warn(
+            &"Different heda version".to_string(),
+            format!(
+                "{} was made by heda {}, so its files may need heda migrate.",
+                style_path(&snapshot.path, "snapshot"),
+                snapshot.manifest.hedaVersion
+            ),
+        );
+    }
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        std::process::exit(1);
+    }
+
+    // the current project is snapshotted first, so restoring can be undone the same way
+    let current_path = make_snapshot(&workspace);
+    snapshot.restore(&workspace.root_directory);
+    for config in &workspace.datasets {
+        snapshot.remove_added_data_files(&workspace.root_directory, &config.data_json);
+    }
+    println!(
+        "{} Restored {} files from {}, after saving the current ones to {}",
+        console::style("Success:").green(),
+        snapshot.manifest.files.len(),
+        style_path(&snapshot.path, "snapshot"),
+        style_path(&current_path, "snapshot")
+    );
+}
+
+fn add_damaged_errs(running_errors: &mut RunningErrors, snapshot: &Snapshot) {
+    for (path_str, err) in snapshot.get_damaged_files() {
+        running_errors.add_err(
+            &"Damaged snapshot".to_string(),
+            format!(
+                "Could not read {path_str} from {}, since blob {err}.",
+                style_path(&snapshot.path, "snapshot")
+            ),
+        );
+    }
+}
+
+// reads back every blob of every snapshot, so corruption is found before a restore needs it
+pub fn run_verify(config_path: &Path) {
+    let workspace = Workspace::new(config_path);
+    let (snapshots, errs) = Snapshot::list(&workspace.backups_directory);
+    let mut running_errors = RunningErrors::new();
+    for err in errs {
+        running_errors.add_err(&"Damaged snapshot".to_string(), err);
+    }
+    for snapshot in &snapshots {
+        add_damaged_errs(&mut running_errors, snapshot);
+    }
+    let unused_blobs = get_unused_blobs(&workspace.backups_directory);
+    if !unused_blobs.is_empty() {
+        running_errors.add_warn(
+            &"Unused blobs".to_string(),
+            format!(
+                "{} blobs in {} aren't in any snapshot.",
+                unused_blobs.len(),
+                style_path(&workspace.backups_directory, "backups directory")
+            ),
+        );
+    }
+    running_errors.print_errs();
+    if running_errors.has_errs() {
+        std::process::exit(1);
+    }
+    println!(
+        "{} Verified {} snapshots in {}",
+        console::style("Success:").green(),
+        snapshots.len(),
+        style_path(&workspace.backups_directory, "backups directory")
+    );
+}
@@ -0,0 +1,293 @@
+use std::path::{Path, PathBuf};
+
+use crate::shared::{
+    config::Workspace,
+    path::style_path,
+    scripts::{from_dynamic, RhaiSpace},
+};
+
+const TEST_FN_PREFIX: &str = "test_";
+const ASSERT_FAILED: &str = "assert_eq failed";
+
+pub fn run_test(config_path: &Path, dataset_arg: Option<&String>, debug: bool) {
+    let workspace = Workspace::new(config_path);
+
+    // datasets may share a scripts file, which only needs testing once
+    let mut scripts_paths: Vec<&Path> = Vec::new();
+    for config in workspace.select(dataset_arg) {
+        if !scripts_paths.contains(&config.scripts_rhai.as_ref()) {
+            scripts_paths.push(&config.scripts_rhai);
+        }
+    }
+    if let (Some(scripts_rhai), None) = (&workspace.scripts_rhai, dataset_arg) {
+        if !scripts_paths.contains(&scripts_rhai.as_ref()) {
+            scripts_paths.push(scripts_rhai);
+        }
+    }
+
+    let (mut passed_count, mut failed_count) = (0, 0);
+    for scripts_path in scripts_paths {
+        if workspace.is_workspace() {
+            println!("{}", style_path(scripts_path, "scripts"));
+        }
+        let (passed, failed) = run_tests_in(scripts_path, &workspace.root_directory, debug);
+        passed_count += passed;
+        failed_count += failed;
+    }
+
+    println!();
+    if passed_count + failed_count == 0 {
+        println!(
+            "{} No {} functions found",
+            console::style("Warning:").yellow(),
+            console::style(format!("{TEST_FN_PREFIX}*")).magenta()
+        );
+    } else if failed_count == 0 {
+        println!(
+            "{} {passed_count} passed",
+            console::style("Success:").green()
+        );
+    } else {
+        println!(
+            "{} {passed_count} passed, {failed_count} failed",
+            console::style("Fatal:").red()
+        );
+        std::process::exit(1);
+    }
+}
+
+// returns the number of tests passed and failed
+fn run_tests_in(scripts_path: &Path, root_directory: &Path, debug: bool) -> (usize, usize) {
+    // tests can live alongside the functions they test, or in a sibling file
+    let mut rhai_space = RhaiSpace::new(scripts_path, root_directory, &[], debug);
+    let tests_path = get_tests_path(scripts_path);
+    if tests_path.is_file() {
+        let tests_ast = rhai_space.engine.compile_file(tests_path.clone()).expect(
+            format!(
+                "{} is a valid rhai script",
+                style_path(&tests_path, "tests file")
+            )
+            .as_str(),
+        );
+        rhai_space.ast.combine(tests_ast);
+    }
+    register_asserts(&mut rhai_space.engine);
+
+    let mut test_names: Vec<String> = Vec::new();
+    for function in rhai_space.ast.iter_functions() {
+        if !function.name.starts_with(TEST_FN_PREFIX) {
+            continue;
+        }
+        // there's nothing to call it with, but it's likely meant as a test
+        if !function.params.is_empty() {
+            println!(
+                "{} Skipped {}, since tests should not take parameters",
+                console::style("Warning:").yellow(),
+                console::style(function.name).magenta()
+            );
+            continue;
+        }
+        test_names.push(function.name.to_string());
+    }
+    test_names.sort();
+
+    let mut failed_count = 0;
+    for test_name in &test_names {
+        let test_res = rhai_space.engine.call_fn::<rhai::Dynamic>(
+            &mut rhai_space.scope,
+            &rhai_space.ast,
+            test_name,
+            (),
+        );
+        match test_res {
+            Ok(_) => println!(
+                "{} ... {}",
+                console::style(test_name).magenta(),
+                console::style("ok").green()
+            ),
+            Err(err) => {
+                println!(
+                    "{} ... {}",
+                    console::style(test_name).magenta(),
+                    console::style("FAILED").red()
+                );
+                println!("{}", fmt_failure(&err));
+                failed_count += 1;
+            }
+        }
+    }
+    (test_names.len() - failed_count, failed_count)
+}
+
+// scripts.rhai -> scripts.test.rhai
+fn get_tests_path(scripts_path: &Path) -> PathBuf {
+    let file_stem = scripts_path
+        .file_stem()
+        .expect(
+            format!(
+                "Should get file stem from {}",
+                style_path(scripts_path, "scripts")
+            )
+            .as_str(),
+        )
+        .to_string_lossy();
+    scripts_path.with_file_name(format!("{file_stem}.test.rhai"))
+}
+
+fn register_asserts(engine: &mut rhai::Engine) {
+    engine.register_fn(
+        "assert_eq",
+        |actual: rhai::Dynamic, expected: rhai::Dynamic| -> Result<(), Box<rhai::EvalAltResult>> {
+            // compare as JSON so maps compare by contents
+            let actual_json = from_dynamic::<serde_json::Value>(&actual)?;
+            let expected_json = from_dynamic::<serde_json::Value>(&expected)?;
+            if json_eq(&actual_json, &expected_json) {
+                return Ok(());
+            }
+            let pretty = |value: &serde_json::Value| {
+                serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+            };
+            Err(format!(
+                "{ASSERT_FAILED}\n{}",
+                diff_lines(&pretty(&expected_json), &pretty(&actual_json))
+            )
+            .into())
+        },
+    );
+}
+
+// like ==, except ints equal their float form, since rhai math turns one into the other
+fn json_eq(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    match (a, b) {
+        (serde_json::Value::Number(a_number), serde_json::Value::Number(b_number)) => {
+            a_number == b_number || a_number.as_f64() == b_number.as_f64()
+        }
+        (serde_json::Value::Array(a_items), serde_json::Value::Array(b_items)) => {
+            a_items.len() == b_items.len()
+                && a_items
+                    .iter()
+                    .zip(b_items)
+                    .all(|(a_item, b_item)| json_eq(a_item, b_item))
+        }
+        (serde_json::Value::Object(a_map), serde_json::Value::Object(b_map)) => {
+            a_map.len() == b_map.len()
+                && a_map.iter().all(|(key, a_value)| {
+                    b_map
+                        .get(key)
+                        .is_some_and(|b_value| json_eq(a_value, b_value))
+                })
+        }
+        _ => a == b,
+    }
+}
+
+fn fmt_failure(err: &rhai::EvalAltResult) -> String {
+    match err {
+        rhai::EvalAltResult::ErrorRuntime(value, pos) => {
+            let message = value.to_string();
+            let diff_legend = if message.starts_with(ASSERT_FAILED) {
+                format!(
+                    " ({} expected, {} actual)",
+                    console::style("-").red(),
+                    console::style("+").green()
+                )
+            } else {
+                String::new()
+            };
+            let (first_line, rest) = message.split_once('\n').unwrap_or((&message, ""));
+            let mut failure = format!("  {first_line} at {pos}{diff_legend}");
+            for line in rest.lines() {
+                failure.push_str(format!("\n  {line}").as_str());
+            }
+            failure
+        }
+        rhai::EvalAltResult::ErrorInFunctionCall(_, _, inner, _) => fmt_failure(inner),
+        other => format!("  {other}"),
+    }
+}
+
+// line diff via longest common subsequence; test fixtures are small
+fn diff_lines(expected: &str, actual: &str) -> String {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let (n, m) = (expected_lines.len(), actual_lines.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected_lines[i] == actual_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff: Vec<String> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected_lines[i] == actual_lines[j] {
+            diff.push(format!("  {}", expected_lines[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(
+                console::style(format!("- {}", expected_lines[i]))
+                    .red()
+                    .to_string(),
+            );
+            i += 1;
+        } else {
+            diff.push(
+                console::style(format!("+ {}", actual_lines[j]))
+                    .green()
+                    .to_string(),
+            );
+            j += 1;
+        }
+    }
+    diff.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn json_numbers_equal_by_value() {
+        assert!(json_eq(&json!(1), &json!(1.0)));
+        assert!(json_eq(&json!(-2.0), &json!(-2)));
+        assert!(!json_eq(&json!(1), &json!(1.5)));
+        assert!(!json_eq(&json!(1), &json!("1")));
+    }
+
+    #[test]
+    fn json_arrays_and_objects_compare_recursively() {
+        assert!(json_eq(
+            &json!({ "a": [1, { "b": 2.0 }], "c": null }),
+            &json!({ "c": null, "a": [1.0, { "b": 2 }] })
+        ));
+        assert!(!json_eq(&json!([1, 2]), &json!([2, 1])));
+        assert!(!json_eq(&json!([1]), &json!([1, 1])));
+        assert!(!json_eq(&json!({ "a": 1 }), &json!({ "a": 1, "b": 1 })));
+        assert!(!json_eq(
+            &json!({ "a": { "b": 1 } }),
+            &json!({ "a": { "c": 1 } })
+        ));
+    }
+
+    #[test]
+    fn lines_diff_by_longest_common_subsequence() {
+        let diff = diff_lines("a\nb\nc\nd", "a\nc\nx\nd\ne");
+        assert_eq!(
+            console::strip_ansi_codes(&diff),
+            "  a\n- b\n  c\n+ x\n  d\n+ e"
+        );
+        assert_eq!(console::strip_ansi_codes(&diff_lines("a", "a")), "  a");
+        assert_eq!(
+            console::strip_ansi_codes(&diff_lines("a\nb", "")),
+            "- a\n- b"
+        );
+    }
+}
@@ -1,12 +1,144 @@
-use std::path::Path;
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::shared::{
+    config::{Config, Workspace},
+    data::get_data_paths,
+    datum::{Collections, Datum},
+    issues::assert_no_errors,
+    keys::get_key_issues,
+    scripts::{to_dynamic, RhaiSpace, ScriptFn},
+};
 
 pub mod derive;
 pub mod plot;
 pub mod sort;
 
-pub fn run_watch(_config_path: &Path) {
-    println!("todo watch");
-    derive::run_derive();
-    sort::run_sort();
-    plot::run_plot();
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+pub const WATCH_FNS: [ScriptFn; 2] = [ScriptFn::Derive, ScriptFn::Sort];
+
+pub fn run_watch(config_path: &Path, dataset_arg: Option<&String>, debug: bool) {
+    let mut watched_paths: Vec<PathBuf> = vec![config_path.to_path_buf()];
+    loop {
+        // a failed run still reports what it found, so fixes to any of those files rerun it
+        let mut found_paths: Vec<PathBuf> = Vec::new();
+        let run_res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            run_pipeline(config_path, dataset_arg, debug, &mut found_paths)
+        }));
+        match run_res {
+            Ok(_) => {
+                watched_paths = found_paths;
+                println!(
+                    "{} Ran pipeline at {}",
+                    console::style("Success:").green(),
+                    chrono::Local::now().format("%H:%M:%S")
+                );
+            }
+            Err(_) => {
+                for found_path in found_paths {
+                    if !watched_paths.contains(&found_path) {
+                        watched_paths.push(found_path);
+                    }
+                }
+            }
+        }
+        println!("Waiting for changes...");
+        wait_for_change(&watched_paths);
+    }
+}
+
+fn run_pipeline(
+    config_path: &Path,
+    dataset_arg: Option<&String>,
+    debug: bool,
+    found_paths: &mut Vec<PathBuf>,
+) {
+    found_paths.push(config_path.to_path_buf());
+    let workspace = Workspace::new(config_path);
+    let configs = workspace.select(dataset_arg);
+    let mut loaded: Vec<(RhaiSpace, Vec<Datum>)> = configs
+        .iter()
+        .map(|config| load_dataset(config, debug, found_paths))
+        .collect();
+    let key_issues = get_key_issues(
+        &configs
+            .iter()
+            .zip(&loaded)
+            .map(|(config, (_, data))| (*config, data.as_slice()))
+            .collect::<Vec<(&Config, &[Datum])>>(),
+    );
+    assert_no_errors("Key", &key_issues);
+
+    let mut collections = Collections::new();
+    for (config, (rhai_space, data)) in configs.iter().zip(loaded.iter_mut()) {
+        let derived_data = derive::run_derive(rhai_space, to_dynamic(&*data));
+        let sorted_data = sort::run_sort(rhai_space, derived_data);
+        plot::run_plot(rhai_space, sorted_data);
+        if let Some(name) = &config.name {
+            collections.insert(name.clone(), std::mem::take(data));
+        }
+    }
+
+    // scripts across datasets need all of them, so are skipped when one is picked
+    if let (Some(scripts_rhai), None) = (&workspace.scripts_rhai, dataset_arg) {
+        found_paths.push(scripts_rhai.to_path_buf());
+        let mut rhai_space = RhaiSpace::new(scripts_rhai, &workspace.root_directory, &[], debug);
+        found_paths.extend(rhai_space.imported_paths());
+        assert_no_errors("Validation", &rhai_space.validate_collections(&collections));
+        if rhai_space.has_fn(ScriptFn::Derive) {
+            let mut derived_data = derive::run_derive(&mut rhai_space, to_dynamic(&collections));
+            if rhai_space.has_fn(ScriptFn::Sort) {
+                derived_data = sort::run_sort(&mut rhai_space, derived_data);
+            }
+            plot::run_plot(&mut rhai_space, derived_data);
+        }
+    }
+}
+
+// validated, but not yet derived, so keys can be checked across datasets first
+fn load_dataset<'a>(
+    config: &Config,
+    debug: bool,
+    found_paths: &mut Vec<PathBuf>,
+) -> (RhaiSpace<'a>, Vec<Datum>) {
+    // a data directory changes when files are added or removed, and each file when edited
+    if config.data_json.is_dir() {
+        found_paths.push(config.data_json.to_path_buf());
+    }
+    found_paths.extend(get_data_paths(&config.data_json).unwrap_or_default());
+    found_paths.push(config.schema_json.to_path_buf());
+    found_paths.push(config.scripts_rhai.to_path_buf());
+
+    let mut rhai_space = RhaiSpace::new(
+        &config.scripts_rhai,
+        &config.root_directory,
+        &WATCH_FNS,
+        debug,
+    );
+    found_paths.extend(rhai_space.imported_paths());
+
+    let data = config.read_validated_data(&mut rhai_space);
+    (rhai_space, data)
+}
+
+fn wait_for_change(paths: &[PathBuf]) {
+    let get_modified_times = || -> Vec<Option<SystemTime>> {
+        paths
+            .iter()
+            .map(|path| {
+                std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    };
+    let modified_times = get_modified_times();
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if get_modified_times() != modified_times {
+            return;
+        }
+    }
 }
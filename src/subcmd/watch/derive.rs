@@ -1,3 +1,5 @@
-pub fn run_derive() {
-    println!("todo derive");
+use crate::shared::scripts::{RhaiSpace, ScriptFn};
+
+pub fn run_derive(rhai_space: &mut RhaiSpace, data: rhai::Dynamic) -> rhai::Dynamic {
+    rhai_space.call_fn::<rhai::Dynamic>(ScriptFn::Derive, (data,))
 }
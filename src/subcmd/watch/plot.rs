@@ -1,3 +1,5 @@
-pub fn run_plot() {
+use crate::shared::scripts::RhaiSpace;
+
+pub fn run_plot(_rhai_space: &mut RhaiSpace, _sorted_data: rhai::Dynamic) {
     println!("todo plot");
 }
@@ -1,3 +1,5 @@
-pub fn run_sort() {
-    println!("todo sort");
+use crate::shared::scripts::{RhaiSpace, ScriptFn};
+
+pub fn run_sort(rhai_space: &mut RhaiSpace, derived_data: rhai::Dynamic) -> rhai::Dynamic {
+    rhai_space.call_fn::<rhai::Dynamic>(ScriptFn::Sort, (derived_data,))
 }
@@ -0,0 +1,57 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// # Person
+///
+/// Someone tracked by the project.
+#[derive(Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Datum {
+    #[serde(default)]
+    pub active: bool,
+    /// Where they live
+    pub address: Address,
+    #[serde(default)]
+    pub backup: Address,
+    pub born: NaiveDate,
+    pub counts: BTreeMap<String, usize>,
+    pub extra: Value,
+    #[serde(rename = "firstName")]
+    pub first_name: String,
+    /// Unique across every person
+    pub id: u32,
+    pub initial: char,
+    #[serde(rename = "nickName")]
+    pub nick_name: Option<String>,
+    pub notes: BTreeMap<String, Option<String>>,
+    pub previous: Option<Address>,
+    pub ratio: f32,
+    #[serde(default)]
+    pub scores: Vec<f64>,
+    pub status: Status,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    #[serde(rename = "type")]
+    pub type_: i8,
+    pub updated: Option<DateTime<Utc>>,
+}
+
+#[derive(Default, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Address {
+    pub city: String,
+    pub zip: Option<i64>,
+}
+
+/// Whether they are still around
+#[derive(Default, Serialize, Deserialize, schemars::JsonSchema)]
+pub enum Status {
+    #[default]
+    #[serde(rename = "active")]
+    Active,
+    #[serde(rename = "on_leave")]
+    OnLeave,
+    #[serde(rename = "gone")]
+    Gone,
+}